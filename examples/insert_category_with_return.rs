@@ -12,6 +12,7 @@ use rustorm::platform::postgres::Postgres;
 use rustorm::query::Query;
 use rustorm::query::{Filter,Equality};
 use rustorm::dao::{Dao,IsDao};
+use rustorm::decimal::Decimal;
 
 #[derive(Debug, Clone)]
 struct Category {
@@ -24,7 +25,7 @@ struct Category {
     pub description:Option<String>,
     pub help:Option<String>,
     pub organization_id:Option<Uuid>,
-    pub priority:Option<f64>,
+    pub priority:Option<Decimal>,
     pub updated:DateTime<UTC>,
     pub updated_by:Option<Uuid>,
 }
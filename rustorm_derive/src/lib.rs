@@ -0,0 +1,385 @@
+//! Derive `IsTable` and `IsDao` for hand written structs,
+//! such as structs mapped to views or to ad hoc projections.
+//!
+//! ```rust,ignore
+//! #[macro_use]
+//! extern crate rustorm_derive;
+//!
+//! #[derive(Debug, Clone, IsTable, IsDao)]
+//! #[rustorm(table = "product", schema = "bazaar")]
+//! pub struct ProductSummary {
+//!     #[rustorm(primary)]
+//!     pub product_id: Uuid,
+//!     #[rustorm(column = "name")]
+//!     pub product_name: String,
+//!     pub description: Option<String>,
+//!     #[rustorm(skip)]
+//!     pub selected: bool,
+//! }
+//! ```
+//!
+//! Attributes on the struct:
+//!
+//! * `table` the table name, defaults to the struct name in snake_case
+//! * `schema` the schema name, defaults to `public`
+//!
+//! Attributes on the fields:
+//!
+//! * `column` the column name, defaults to the field name
+//! * `primary` the field is the primary key
+//! * `unique` the field has a unique constraint
+//! * `db_type` the database data type, when it can not be derived from the rust type
+//! * `version` the column used for optimistic locking, an integer or a timestamp field,
+//!   defaults to the column named `version`, other columns ie: an `updated` timestamp need this attribute
+//! * `skip` the field is not a column, it is set with `Default::default()` in `from_dao`
+//!
+//! `Option` fields are nullable columns, other fields are `not null`.
+//! The database data type is derived from the rust type when the macro is expanded,
+//! a type with no postgresql equivalent is a compile error unless `db_type` is specified.
+//! Deriving `IsDao` also implements `ToDao`, the reverse conversion used by `Session`.
+extern crate proc_macro;
+extern crate syn;
+#[macro_use]
+extern crate quote;
+
+use proc_macro::TokenStream;
+use syn::{MacroInput, Body, VariantData, Field, Ty, Ident};
+use syn::{Attribute, MetaItem, NestedMetaItem, Lit, PathParameters};
+
+/// the same mapping of the rust types to postgresql data types used by `Postgres::rust_type_to_dbtype`
+#[path = "../../src/platform/pg_rust_type.rs"]
+mod pg_rust_type;
+use pg_rust_type::db_type;
+
+/// the name used in the attributes ie: `#[rustorm(table = "product")]`
+const ATTRIBUTE: &'static str = "rustorm";
+
+#[proc_macro_derive(IsTable, attributes(rustorm))]
+pub fn derive_is_table(input: TokenStream)->TokenStream{
+    let ast = parse(input);
+    let expanded = impl_is_table(&ast);
+    expanded.parse().unwrap()
+}
+
+#[proc_macro_derive(IsDao, attributes(rustorm))]
+pub fn derive_is_dao(input: TokenStream)->TokenStream{
+    let ast = parse(input);
+    let expanded = impl_is_dao(&ast);
+    expanded.parse().unwrap()
+}
+
+fn parse(input: TokenStream)->MacroInput{
+    let source = input.to_string();
+    match syn::parse_macro_input(&source){
+        Ok(ast) => ast,
+        Err(e) => panic!("Unable to parse the struct due to {}", e),
+    }
+}
+
+/// a member of the struct mapped to a column
+struct ColumnField<'a>{
+    field: &'a Field,
+    /// the name of the column
+    column: String,
+    /// the type inside the Option, or the type itself when not optional
+    data_type: String,
+    db_type: Option<String>,
+    is_optional: bool,
+    is_primary: bool,
+    is_unique: bool,
+    is_version: bool,
+    skip: bool,
+}
+
+impl <'a>ColumnField<'a>{
+    fn from_field(field: &'a Field)->Self{
+        let ident = field.ident.as_ref().expect("Only structs with named fields are supported");
+        let (is_optional, data_type) = match option_type(&field.ty){
+            Some(ty) => (true, short_type_name(ty)),
+            None => (false, short_type_name(&field.ty)),
+        };
+        let mut column = ColumnField{
+            field: field,
+            column: ident.to_string(),
+            data_type: data_type,
+            db_type: None,
+            is_optional: is_optional,
+            is_primary: false,
+            is_unique: false,
+            is_version: false,
+            skip: false,
+        };
+        for item in attribute_items(&field.attrs){
+            match item{
+                MetaItem::Word(ref name) if *name == "primary" => column.is_primary = true,
+                MetaItem::Word(ref name) if *name == "unique" => column.is_unique = true,
+                MetaItem::Word(ref name) if *name == "version" => column.is_version = true,
+                MetaItem::Word(ref name) if *name == "skip" => column.skip = true,
+                MetaItem::NameValue(ref name, Lit::Str(ref value, _)) if *name == "column" => {
+                    column.column = value.to_string()
+                }
+                MetaItem::NameValue(ref name, Lit::Str(ref value, _)) if *name == "db_type" => {
+                    column.db_type = Some(value.to_string())
+                }
+                _ => panic!("Unknown rustorm attribute on {}", ident),
+            }
+        }
+        column
+    }
+
+    fn ident(&self)->&Ident{
+        self.field.ident.as_ref().unwrap()
+    }
+}
+
+/// the items inside `#[rustorm(..)]`
+fn attribute_items(attrs: &Vec<Attribute>)->Vec<MetaItem>{
+    let mut items = vec![];
+    for attr in attrs{
+        match attr.value{
+            MetaItem::List(ref name, ref nested) if *name == ATTRIBUTE => {
+                for n in nested{
+                    match *n{
+                        NestedMetaItem::MetaItem(ref item) => items.push(item.clone()),
+                        NestedMetaItem::Literal(_) => panic!("Expecting name = \"value\" in rustorm attribute"),
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    items
+}
+
+/// the type T of Option<T>
+fn option_type(ty: &Ty)->Option<&Ty>{
+    match *ty{
+        Ty::Path(None, ref path) => {
+            let segment = match path.segments.last(){
+                Some(segment) => segment,
+                None => return None,
+            };
+            if segment.ident != "Option"{
+                return None;
+            }
+            match segment.parameters{
+                PathParameters::AngleBracketed(ref data) if data.types.len() == 1 => Some(&data.types[0]),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// the type without the spaces and the module paths, the same as the generated code
+/// ie: `chrono::DateTime<chrono::UTC>` is `DateTime<UTC>`
+fn short_type_name(ty: &Ty)->String{
+    match *ty{
+        Ty::Path(None, ref path) if !path.segments.is_empty() => {
+            let segment = path.segments.last().unwrap();
+            match segment.parameters{
+                PathParameters::AngleBracketed(ref data) if !data.types.is_empty() => {
+                    let types: Vec<String> = data.types.iter().map(short_type_name).collect();
+                    format!("{}<{}>", segment.ident, types.join(","))
+                }
+                _ => segment.ident.to_string(),
+            }
+        }
+        _ => {
+            let mut tokens = quote::Tokens::new();
+            quote::ToTokens::to_tokens(ty, &mut tokens);
+            tokens.to_string().replace(" ", "")
+        }
+    }
+}
+
+/// the version of the table from the field with the version attribute
+fn version(column: &ColumnField)->Result<quote::Tokens, String>{
+    let name = &column.column;
+    match &column.data_type[..]{
+        "i16" | "i32" | "i64" => Ok(quote!{ Some(::rustorm::table::Version::Number(#name.to_string())) }),
+        "DateTime<UTC>" | "NaiveDateTime" => Ok(quote!{ Some(::rustorm::table::Version::Timestamp(#name.to_string())) }),
+        _ => Err(format!("The version column {} should be an integer or a timestamp, not {}", name, column.data_type)),
+    }
+}
+
+/// fail the compilation with the message pointing at the derive
+fn compile_error(message: &str)->quote::Tokens{
+    quote!{ compile_error!(#message); }
+}
+
+/// ProductAvailability -> product_availability
+fn snake_case(name: &str)->String{
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate(){
+        if c.is_uppercase(){
+            if i > 0{
+                snake.push('_');
+            }
+            for l in c.to_lowercase(){
+                snake.push(l);
+            }
+        }else{
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+fn struct_fields<'a>(ast: &'a MacroInput)->Vec<ColumnField<'a>>{
+    match ast.body{
+        Body::Struct(VariantData::Struct(ref fields)) => fields.iter().map(|f| ColumnField::from_field(f)).collect(),
+        _ => panic!("IsTable and IsDao can only be derived for structs with named fields"),
+    }
+}
+
+fn impl_is_table(ast: &MacroInput)->quote::Tokens{
+    let name = &ast.ident;
+    let mut table = snake_case(&name.to_string());
+    let mut schema = "public".to_string();
+    for item in attribute_items(&ast.attrs){
+        match item{
+            MetaItem::NameValue(ref key, Lit::Str(ref value, _)) if *key == "table" => table = value.to_string(),
+            MetaItem::NameValue(ref key, Lit::Str(ref value, _)) if *key == "schema" => schema = value.to_string(),
+            _ => panic!("Unknown rustorm attribute on {}", name),
+        }
+    }
+    let fields = struct_fields(ast);
+    let fields: Vec<&ColumnField> = fields.iter().filter(|c| !c.skip).collect();
+    let mut columns = vec![];
+    for c in &fields{
+        let column = &c.column;
+        let data_type = &c.data_type;
+        let db_data_type = match c.db_type.clone().or_else(|| db_type(data_type)){
+            Some(db_data_type) => db_data_type,
+            None => return compile_error(&format!("Unable to get the database data type of {} for {}, specify it with #[rustorm(db_type = \"..\")]", 
+                                    data_type, column)),
+        };
+        let is_primary = c.is_primary;
+        let is_unique = c.is_unique;
+        let not_null = !c.is_optional;
+        columns.push(quote!{
+            ::rustorm::table::Column{
+                name: #column.to_string(),
+                data_type: #data_type.to_string(),
+                db_data_type: #db_data_type.to_string(),
+                is_primary: #is_primary,
+                is_unique: #is_unique,
+                default: None,
+                comment: None,
+                not_null: #not_null,
+                foreign: None,
+                is_inherited: false,
+                precision: None,
+                scale: None,
+            }
+        });
+    }
+    let versions: Vec<&&ColumnField> = fields.iter().filter(|c| c.is_version).collect();
+    let version = match versions.len(){
+        0 => quote!{ ::rustorm::table::Version::detect(&columns) },
+        1 => match version(versions[0]){
+            Ok(version) => version,
+            Err(e) => return compile_error(&e),
+        },
+        _ => return compile_error(&format!("Only 1 version column is allowed in {}", name)),
+    };
+    quote!{
+        impl ::rustorm::table::IsTable for #name{
+            fn table()->::rustorm::table::Table{
+                let columns = vec![#(#columns),*];
+                ::rustorm::table::Table{
+                    schema: #schema.to_string(),
+                    name: #table.to_string(),
+                    parent_table: None,
+                    sub_table: vec![],
+                    comment: None,
+                    version: #version,
+                    columns: columns,
+                }
+            }
+        }
+    }
+}
+
+fn impl_is_dao(ast: &MacroInput)->quote::Tokens{
+    let name = &ast.ident;
+    let fields = struct_fields(ast);
+    let from_dao: Vec<quote::Tokens> = fields
+        .iter()
+        .map(|c|{
+            let ident = c.ident();
+            let column = &c.column;
+            if c.skip{
+                quote!{ #ident: ::std::default::Default::default() }
+            }else if c.is_optional{
+                quote!{ #ident: dao.get_opt(#column) }
+            }else{
+                quote!{ #ident: dao.get(#column) }
+            }
+        })
+        .collect();
+    let to_dao: Vec<quote::Tokens> = fields
+        .iter()
+        .filter(|c| !c.skip)
+        .map(|c|{
+            let ident = c.ident();
+            let column = &c.column;
+            quote!{ dao.set(#column, &self.#ident); }
+        })
+        .collect();
+    quote!{
+        impl ::rustorm::dao::IsDao for #name{
+            fn from_dao(dao: &::rustorm::dao::Dao)->Self{
+                #name{
+                    #(#from_dao),*
+                }
+            }
+        }
+
+        impl ::rustorm::dao::ToDao for #name{
+            fn to_dao(&self)->::rustorm::dao::Dao{
+                let mut dao = ::rustorm::dao::Dao::new();
+                #(#to_dao)*
+                dao
+            }
+        }
+    }
+}
+
+#[test]
+fn test_snake_case(){
+    assert_eq!(snake_case("ProductAvailability"), "product_availability");
+    assert_eq!(snake_case("Product"), "product");
+}
+
+#[test]
+fn test_column_field(){
+    let ast = syn::parse_macro_input("
+        struct Product{
+            #[rustorm(primary)]
+            product_id: Uuid,
+            #[rustorm(column = \"name\")]
+            product_name: Option<String>,
+            created: DateTime<UTC>,
+            #[rustorm(skip)]
+            selected: bool,
+        }").unwrap();
+    let fields = struct_fields(&ast);
+    assert!(fields[0].is_primary);
+    assert_eq!(fields[1].column, "name");
+    assert!(fields[1].is_optional);
+    assert_eq!(fields[1].data_type, "String");
+    assert_eq!(fields[2].data_type, "DateTime<UTC>");
+    assert!(fields[3].skip);
+}
+
+#[test]
+fn test_db_type(){
+    let ty = syn::parse_type("::chrono::DateTime<chrono::UTC>").unwrap();
+    assert_eq!(short_type_name(&ty), "DateTime<UTC>");
+    assert_eq!(db_type(&short_type_name(&ty)), Some("timestamp with time zone".to_string()));
+    let ty = syn::parse_type("Vec<uuid::Uuid>").unwrap();
+    assert_eq!(db_type(&short_type_name(&ty)), Some("uuid[]".to_string()));
+    assert_eq!(db_type("ProductStatus"), None);
+}
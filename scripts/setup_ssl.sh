@@ -0,0 +1,20 @@
+#!/bin/sh
+
+## generate a self-signed certificate for localhost and enable ssl on the local postgres server,
+## the data directory is passed as the first argument ie: /var/lib/postgresql/9.4/main
+## the certificate is also copied to scripts/ssl/root.crt, which is used as sslrootcert in the tests
+PGDATA="$1"
+DIR=`dirname "$0"`/ssl
+mkdir -p "$DIR"
+
+openssl req -new -x509 -days 365 -nodes -subj "/CN=localhost" -addext "subjectAltName=DNS:localhost" \
+    -out "$DIR/server.crt" -keyout "$DIR/server.key"
+cp "$DIR/server.crt" "$DIR/root.crt"
+
+cp "$DIR/server.crt" "$DIR/server.key" "$PGDATA/"
+chmod 600 "$PGDATA/server.key"
+chown postgres:postgres "$PGDATA/server.crt" "$PGDATA/server.key"
+
+## enable ssl and restart the server, will prompt for password
+psql -U postgres -W -h localhost -p 5432 -d postgres -c "alter system set ssl = on;"
+pg_ctl -D "$PGDATA" restart
@@ -0,0 +1,127 @@
+//! Iterate over a large result without loading all the records in memory,
+//! the records are fetched in batches from a server side cursor.
+//!
+//! ```rust,no_run
+//! let mut query = Query::select_all();
+//! query.from_table("bazaar.product");
+//! for product in query.stream_as::<Product>(&pg, 1000).unwrap(){
+//!     println!("{}", product.name);
+//! }
+//! ```
+use database::Database;
+use query::Query;
+use dao::{Dao, IsDao};
+
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+
+/// the number of records fetched at a time when not specified
+pub const DEFAULT_FETCH_SIZE: usize = 1000;
+
+/// used for naming the cursors uniquely
+static CURSOR_COUNT: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Iterates the records of a query, fetching only fetch_size records at a time.
+/// Cursors only live inside a transaction, when there is no transaction yet,
+/// one is started and committed when the cursor is dropped,
+/// otherwise the cursor is part of the existing transaction
+pub struct Cursor<'a>{
+    db: &'a Database,
+    name: String,
+    fetch_size: usize,
+    buffer: VecDeque<Dao>,
+    /// there are no more records to fetch
+    done: bool,
+    /// the transaction is started by this cursor
+    own_transaction: bool,
+}
+
+impl <'a>Cursor<'a>{
+
+    /// declare the cursor for the query, the records are not fetched until iterated
+    pub fn new(db:&'a Database, query:&Query, fetch_size:usize)->Result<Self, String>{
+        assert!(fetch_size > 0, "fetch_size should be greater than 0");
+        let own_transaction = !db.is_transacted();
+        if own_transaction{
+            try!(db.begin());
+        }
+        let name = format!("rustorm_cursor_{}", CURSOR_COUNT.fetch_add(1, Ordering::SeqCst));
+        let sql_frag = db.build_query(query);
+        let declare = format!("DECLARE {} NO SCROLL CURSOR FOR {}", name, sql_frag.sql);
+        match db.execute_sql(&declare, &sql_frag.params){
+            Ok(_) => (),
+            Err(e) => {
+                if own_transaction{
+                    let _ = db.rollback();
+                }
+                return Err(e);
+            }
+        }
+        Ok(Cursor{
+            db: db,
+            name: name,
+            fetch_size: fetch_size,
+            buffer: VecDeque::new(),
+            done: false,
+            own_transaction: own_transaction,
+        })
+    }
+
+    /// fetch the next batch of records from the database
+    fn fetch(&mut self){
+        let sql = format!("FETCH FORWARD {} FROM {}", self.fetch_size, self.name);
+        let dao = self.db.execute_sql_with_return(&sql, &vec![]);
+        if dao.len() < self.fetch_size{
+            self.done = true;
+        }
+        self.buffer.extend(dao);
+    }
+}
+
+impl <'a>Iterator for Cursor<'a>{
+    type Item = Dao;
+
+    fn next(&mut self)->Option<Dao>{
+        if self.buffer.is_empty() && !self.done{
+            self.fetch();
+        }
+        self.buffer.pop_front()
+    }
+}
+
+impl <'a>Drop for Cursor<'a>{
+
+    /// close the cursor, and end the transaction if it was started by this cursor
+    fn drop(&mut self){
+        let close = format!("CLOSE {}", self.name);
+        let closed = self.db.execute_sql(&close, &vec![]);
+        if self.own_transaction{
+            let _ = match closed{
+                Ok(_) => self.db.commit(),
+                Err(_) => self.db.rollback(),
+            };
+        }
+    }
+}
+
+/// the records of the cursor converted into the generated struct
+pub struct EntityCursor<'a, T>{
+    cursor: Cursor<'a>,
+    phantom: PhantomData<T>,
+}
+
+impl <'a, T>EntityCursor<'a, T> where T: IsDao{
+
+    pub fn new(cursor:Cursor<'a>)->Self{
+        EntityCursor{cursor: cursor, phantom: PhantomData}
+    }
+}
+
+impl <'a, T>Iterator for EntityCursor<'a, T> where T: IsDao{
+    type Item = T;
+
+    fn next(&mut self)->Option<T>{
+        self.cursor.next().map(|dao| T::from_dao(&dao))
+    }
+}
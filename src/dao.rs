@@ -1,16 +1,25 @@
 use std::collections::HashMap;
 use std::collections::BTreeMap;
+use std::vec::IntoIter;
 use uuid::Uuid;
 use chrono::datetime::DateTime;
 use chrono::naive::date::NaiveDate;
 use chrono::naive::time::NaiveTime;
 use chrono::naive::datetime::NaiveDateTime;
 use chrono::offset::utc::UTC;
+use rustc_serialize::json::Json;
+use decimal::Decimal;
+use dao_serde::DaoError;
+use types::{Interval, Inet, MacAddr, Money, Point, Range, RangeBound};
+use rustc_serialize::{Encodable, Encoder, Decodable, Decoder};
 use std::fmt;
+use std::ops::Deref;
+use std::mem;
 
 #[derive(RustcDecodable, RustcEncodable)]
 #[derive(Debug)]
 #[derive(Clone)]
+#[derive(PartialEq)]
 ///supported generic datatypes for an ORM
 pub enum Type{
     Bool(bool),
@@ -24,6 +33,8 @@ pub enum Type{
     U64(u64),
     F32(f32),
     F64(f64),
+    /// exact numeric
+    Decimal(Decimal),
     String(String),
     VecU8(Vec<u8>),
     Map(HashMap<String, Option<String>>),
@@ -32,9 +43,78 @@ pub enum Type{
     NaiveDate(NaiveDate),
     NaiveTime(NaiveTime),
     NaiveDateTime(NaiveDateTime),
+    /// json and jsonb
+    Json(JsonValue),
+    /// one dimensional arrays ie: `text[]`, `integer[]`
+    Array(Vec<Type>),
+    /// the label of a user defined enum
+    Enum(String),
+    /// the fields of a user defined composite type, in the order of its attributes
+    Composite(Vec<Type>),
+    Interval(Interval),
+    /// inet and cidr
+    Inet(Inet),
+    MacAddr(MacAddr),
+    Money(Money),
+    Point(Point),
+    /// tsrange, tstzrange, daterange, int4range, int8range and numrange
+    Range(Box<Range<Type>>),
     Null,
 }
 
+/// a json or jsonb value,
+/// rustc_serialize Json is not Decodable, so it is wrapped and encoded as the json text
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub struct JsonValue(pub Json);
+
+impl Deref for JsonValue{
+    type Target = Json;
+
+    fn deref(&self)->&Json{
+        &self.0
+    }
+}
+
+impl fmt::Display for JsonValue{
+    fn fmt(&self, f: &mut fmt::Formatter)->fmt::Result{
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Encodable for JsonValue{
+    fn encode<S: Encoder>(&self, s: &mut S)->Result<(), S::Error>{
+        s.emit_str(&self.0.to_string())
+    }
+}
+
+impl Decodable for JsonValue{
+    fn decode<D: Decoder>(d: &mut D)->Result<Self, D::Error>{
+        let text = try!(d.read_str());
+        match Json::from_str(&text){
+            Ok(json) => Ok(JsonValue(json)),
+            Err(e) => Err(d.error(&format!("Invalid json {} due to {}", text, e))),
+        }
+    }
+}
+
+impl Type{
+
+    /// the value used when comparing keys, the signed and the smaller unsigned integers
+    /// are widened to i64, so an int4 foreign key matches an int8 primary key
+    pub fn key_value(&self)->Type{
+        match *self{
+            Type::I8(x) => Type::I64(x as i64),
+            Type::I16(x) => Type::I64(x as i64),
+            Type::I32(x) => Type::I64(x as i64),
+            Type::U8(x) => Type::I64(x as i64),
+            Type::U16(x) => Type::I64(x as i64),
+            Type::U32(x) => Type::I64(x as i64),
+            _ => self.clone(),
+        }
+    }
+}
 
 impl fmt::Display for Type{
     
@@ -81,6 +161,28 @@ impl fmt::Display for Type{
 pub trait ToType{
     fn to_db_type(&self)->Type;
 }
+
+impl ToType for Type{
+    fn to_db_type(&self)->Type{
+        self.clone()
+    }
+}
+
+/// None is NULL
+impl <T:ToType>ToType for Option<T>{
+    fn to_db_type(&self)->Type{
+        match *self{
+            Some(ref x) => x.to_db_type(),
+            None => Type::Null,
+        }
+    }
+}
+
+impl ToType for bool{
+    fn to_db_type(&self)->Type{
+        Type::Bool(self.clone())
+    }
+}
 /// signed INTs
 impl ToType for i8{
     fn to_db_type(&self)->Type{
@@ -128,6 +230,24 @@ impl ToType for u64{
     }
 }
 
+impl ToType for f32{
+    fn to_db_type(&self)->Type{
+        Type::F32(self.clone())
+    }
+}
+
+impl ToType for f64{
+    fn to_db_type(&self)->Type{
+        Type::F64(self.clone())
+    }
+}
+
+impl ToType for Decimal{
+    fn to_db_type(&self)->Type{
+        Type::Decimal(self.clone())
+    }
+}
+
 impl <'a>ToType for &'a str{
     fn to_db_type(&self)->Type{
         Type::String(self.to_string())
@@ -169,6 +289,60 @@ impl ToType for NaiveDateTime{
     }
 }
 
+impl <T:ToType>ToType for Vec<T>{
+    fn to_db_type(&self)->Type{
+        Type::Array(self.iter().map(|x| x.to_db_type()).collect())
+    }
+}
+
+impl ToType for JsonValue{
+    fn to_db_type(&self)->Type{
+        Type::Json(self.clone())
+    }
+}
+
+impl ToType for Json{
+    fn to_db_type(&self)->Type{
+        Type::Json(JsonValue(self.clone()))
+    }
+}
+
+impl ToType for Interval{
+    fn to_db_type(&self)->Type{
+        Type::Interval(self.clone())
+    }
+}
+
+impl ToType for Inet{
+    fn to_db_type(&self)->Type{
+        Type::Inet(self.clone())
+    }
+}
+
+impl ToType for MacAddr{
+    fn to_db_type(&self)->Type{
+        Type::MacAddr(self.clone())
+    }
+}
+
+impl ToType for Money{
+    fn to_db_type(&self)->Type{
+        Type::Money(self.clone())
+    }
+}
+
+impl ToType for Point{
+    fn to_db_type(&self)->Type{
+        Type::Point(self.clone())
+    }
+}
+
+impl <T:ToType>ToType for Range<T>{
+    fn to_db_type(&self)->Type{
+        Type::Range(Box::new(self.map(|x| x.to_db_type())))
+    }
+}
+
 ///
 ///
 ///
@@ -184,150 +358,443 @@ impl ToType for NaiveDateTime{
 ///
 ///
 pub trait FromType{
+
+    /// panics when the type can not be converted
     fn from_type(ty:Type)->Self;
+
+    /// the value or a description of why the type can not be converted,
+    /// the implementations which only have from_type panic instead
+    fn try_from_type(ty:Type)->Result<Self, String> where Self: Sized{
+        Ok(Self::from_type(ty))
+    }
+}
+
+/// the converted value of try_from_type, panics with the error
+fn expect_type<T>(result:Result<T, String>)->T{
+    match result{
+        Ok(x) => x,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+fn mismatch<T>(expected:&str, ty:&Type)->Result<T, String>{
+    Err(format!("expecting {} but got {:?}", expected, ty))
+}
+
+impl FromType for Type{
+    fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
+        Ok(ty)
+    }
+}
+
+/// NULL is None
+impl <T:FromType>FromType for Option<T>{
+    fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
+        match ty{
+            Type::Null => Ok(None),
+            _ => T::try_from_type(ty).map(Some),
+        }
+    }
 }
 
 impl FromType for bool{
     fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
         match ty{
-            Type::Bool(x) => x,
-            _ => panic!("error!"),
+            Type::Bool(x) => Ok(x),
+            _ => mismatch("bool", &ty),
         }
     }
 }
 
 impl FromType for i8{
     fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
         match ty{
-            Type::I8(x) => x,
-            _ => panic!("error!"),
+            Type::I8(x) => Ok(x),
+            _ => mismatch("i8", &ty),
         }
     }
 }
+
+/// the smaller integer types are widened
 impl FromType for i16{
     fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
         match ty{
-            Type::I16(x) => x,
-            _ => panic!("error!"),
+            Type::I16(x) => Ok(x),
+            Type::I8(x) => Ok(x as i16),
+            Type::U8(x) => Ok(x as i16),
+            _ => mismatch("i16", &ty),
         }
     }
 }
+
+/// the smaller integer types are widened
 impl FromType for i32{
     fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
         match ty{
-            Type::I32(x) => x,
-            _ => panic!("error!"),
+            Type::I32(x) => Ok(x),
+            Type::I16(x) => Ok(x as i32),
+            Type::I8(x) => Ok(x as i32),
+            Type::U16(x) => Ok(x as i32),
+            Type::U8(x) => Ok(x as i32),
+            _ => mismatch("i32", &ty),
         }
     }
 }
+
+/// the smaller integer types are widened
 impl FromType for i64{
     fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
         match ty{
-            Type::I64(x) => x,
-            _ => panic!("error!"),
+            Type::I64(x) => Ok(x),
+            Type::I32(x) => Ok(x as i64),
+            Type::I16(x) => Ok(x as i64),
+            Type::I8(x) => Ok(x as i64),
+            Type::U32(x) => Ok(x as i64),
+            Type::U16(x) => Ok(x as i64),
+            Type::U8(x) => Ok(x as i64),
+            _ => mismatch("i64", &ty),
         }
     }
 }
+
 impl FromType for u8{
     fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
         match ty{
-            Type::U8(x) => x,
-            _ => panic!("error!"),
+            Type::U8(x) => Ok(x),
+            _ => mismatch("u8", &ty),
         }
     }
 }
+
+/// the smaller integer types are widened
 impl FromType for u16{
     fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
         match ty{
-            Type::U16(x) => x,
-            _ => panic!("error!"),
+            Type::U16(x) => Ok(x),
+            Type::U8(x) => Ok(x as u16),
+            _ => mismatch("u16", &ty),
         }
     }
 }
+
+/// the smaller integer types are widened
 impl FromType for u32{
     fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
         match ty{
-            Type::U32(x) => x,
-            _ => panic!("error!"),
+            Type::U32(x) => Ok(x),
+            Type::U16(x) => Ok(x as u32),
+            Type::U8(x) => Ok(x as u32),
+            _ => mismatch("u32", &ty),
         }
     }
 }
+
+/// the smaller integer types are widened
 impl FromType for u64{
     fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
         match ty{
-            Type::U64(x) => x,
-            _ => panic!("error!"),
+            Type::U64(x) => Ok(x),
+            Type::U32(x) => Ok(x as u64),
+            Type::U16(x) => Ok(x as u64),
+            Type::U8(x) => Ok(x as u64),
+            _ => mismatch("u64", &ty),
         }
     }
 }
 
 impl FromType for f32{
     fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
         match ty{
-            Type::F32(x) => x,
-            _ => panic!("error!"),
+            Type::F32(x) => Ok(x),
+            _ => mismatch("f32", &ty),
         }
     }
 }
+
+/// f32 is widened to f64
 impl FromType for f64{
     fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
         match ty{
-            Type::F64(x) => x,
-            _ => panic!("error!"),
+            Type::F64(x) => Ok(x),
+            Type::F32(x) => Ok(x as f64),
+            _ => mismatch("f64", &ty),
+        }
+    }
+}
+
+impl FromType for Decimal{
+    fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
+        match ty{
+            Type::Decimal(x) => Ok(x),
+            _ => mismatch("Decimal", &ty),
         }
     }
 }
 
 impl FromType for String{
     fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
         match ty{
-            Type::String(x) => x,
-            _ => panic!("error! {:?}", ty),
+            Type::String(x) => Ok(x),
+            _ => mismatch("String", &ty),
         }
     }
 }
 
 impl FromType for Uuid{
     fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
         match ty{
-            Type::Uuid(x) => x,
-            _ => panic!("error!"),
+            Type::Uuid(x) => Ok(x),
+            _ => mismatch("Uuid", &ty),
         }
     }
 }
 
 impl FromType for DateTime<UTC>{
     fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
         match ty{
-            Type::DateTime(x) => x,
-            _ => panic!("error!"),
+            Type::DateTime(x) => Ok(x),
+            _ => mismatch("DateTime<UTC>", &ty),
         }
     }
 }
 
 impl FromType for NaiveTime{
     fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
         match ty{
-            Type::NaiveTime(x) => x,
-            _ => panic!("error!"),
+            Type::NaiveTime(x) => Ok(x),
+            _ => mismatch("NaiveTime", &ty),
         }
     }
 }
 
 impl FromType for NaiveDate{
     fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
         match ty{
-            Type::NaiveDate(x) => x,
-            _ => panic!("error!"),
+            Type::NaiveDate(x) => Ok(x),
+            _ => mismatch("NaiveDate", &ty),
         }
     }
 }
 
 impl FromType for NaiveDateTime{
     fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
+        match ty{
+            Type::NaiveDateTime(x) => Ok(x),
+            _ => mismatch("NaiveDateTime", &ty),
+        }
+    }
+}
+
+/// bytea is also read as Vec<u8>
+impl <T:FromType>FromType for Vec<T>{
+    fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
         match ty{
-            Type::NaiveDateTime(x) => x,
-            _ => panic!("error!"),
+            Type::Array(x) => x.into_iter().map(|v| T::try_from_type(v)).collect(),
+            Type::VecU8(x) => x.into_iter().map(|v| T::try_from_type(Type::U8(v))).collect(),
+            _ => mismatch("an array", &ty),
+        }
+    }
+}
+
+impl FromType for JsonValue{
+    fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
+        match ty{
+            Type::Json(x) => Ok(x),
+            _ => mismatch("json", &ty),
+        }
+    }
+}
+
+impl FromType for Json{
+    fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
+        match ty{
+            Type::Json(JsonValue(x)) => Ok(x),
+            _ => mismatch("json", &ty),
+        }
+    }
+}
+
+impl FromType for Interval{
+    fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
+        match ty{
+            Type::Interval(x) => Ok(x),
+            _ => mismatch("Interval", &ty),
+        }
+    }
+}
+
+impl FromType for Inet{
+    fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
+        match ty{
+            Type::Inet(x) => Ok(x),
+            _ => mismatch("Inet", &ty),
+        }
+    }
+}
+
+impl FromType for MacAddr{
+    fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
+        match ty{
+            Type::MacAddr(x) => Ok(x),
+            _ => mismatch("MacAddr", &ty),
+        }
+    }
+}
+
+impl FromType for Money{
+    fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
+        match ty{
+            Type::Money(x) => Ok(x),
+            _ => mismatch("Money", &ty),
+        }
+    }
+}
+
+impl FromType for Point{
+    fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
+        match ty{
+            Type::Point(x) => Ok(x),
+            _ => mismatch("Point", &ty),
+        }
+    }
+}
+
+fn try_from_bound<T:FromType>(bound:RangeBound<Type>)->Result<RangeBound<T>, String>{
+    match bound{
+        RangeBound::Inclusive(x) => T::try_from_type(x).map(RangeBound::Inclusive),
+        RangeBound::Exclusive(x) => T::try_from_type(x).map(RangeBound::Exclusive),
+        RangeBound::Unbounded => Ok(RangeBound::Unbounded),
+    }
+}
+
+impl <T:FromType>FromType for Range<T>{
+    fn from_type(ty:Type)->Self{
+        expect_type(Self::try_from_type(ty))
+    }
+
+    fn try_from_type(ty:Type)->Result<Self, String>{
+        match ty{
+            Type::Range(x) => match *x{
+                Range::Empty => Ok(Range::Empty),
+                Range::Between(lower, upper) => {
+                    let lower = try!(try_from_bound(lower));
+                    let upper = try!(try_from_bound(upper));
+                    Ok(Range::Between(lower, upper))
+                },
+            },
+            _ => mismatch("a range", &ty),
         }
     }
 }
@@ -351,6 +818,23 @@ pub trait IsDao:Sized + Clone{
     fn from_dao(dao: &Dao)->Self;
 }
 
+/// trait for converting model to dao, the reverse of IsDao
+/// used when tracking changes of the instance
+pub trait ToDao{
+
+    /// convert this instance into a dao
+    fn to_dao(&self)->Dao;
+}
+
+/// trait for models that has the referenced tables as members
+/// ie. has one, extension and has many members of the generated struct
+pub trait HasRelation{
+    
+    /// set the member of this struct using the records of the referenced table,
+    /// has one and extension members use only the first record, if there is any
+    fn set_relation(&mut self, member:&str, dao:&Vec<Dao>);
+}
+
 /// meta result of a query useful when doing complex query, and also with paging
 #[derive(Debug)]
 pub struct DaoResult{
@@ -367,22 +851,135 @@ pub struct DaoResult{
     pub page_size: Option<usize>,
 }
 
+/// the values of the columns in the order they are set,
+/// which is the order of the select in query results.
+/// Looked up by column name like a map, a record only has a few columns
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(Default)]
+pub struct Values{
+    entries:Vec<(String, Type)>,
+}
+
+impl Values{
+
+    pub fn new()->Self{
+        Values{entries:vec![]}
+    }
+
+    fn position(&self, column:&str)->Option<usize>{
+        self.entries.iter().position(|e| e.0 == column)
+    }
+
+    pub fn get(&self, column:&str)->Option<&Type>{
+        self.position(column).map(|i| &self.entries[i].1)
+    }
+
+    pub fn get_mut(&mut self, column:&str)->Option<&mut Type>{
+        match self.position(column){
+            Some(i) => Some(&mut self.entries[i].1),
+            None => None,
+        }
+    }
+
+    pub fn contains_key(&self, column:&str)->bool{
+        self.position(column).is_some()
+    }
+
+    /// setting an existing column keeps its position, returns the previous value
+    pub fn insert(&mut self, column:String, value:Type)->Option<Type>{
+        match self.position(&column){
+            Some(i) => Some(mem::replace(&mut self.entries[i].1, value)),
+            None => {
+                self.entries.push((column, value));
+                None
+            },
+        }
+    }
+
+    pub fn remove(&mut self, column:&str)->Option<Type>{
+        self.position(column).map(|i| self.entries.remove(i).1)
+    }
+
+    /// the column names in order
+    pub fn keys(&self)->IntoIter<&str>{
+        let keys:Vec<&str> = self.entries.iter().map(|e| &e.0[..]).collect();
+        keys.into_iter()
+    }
+
+    /// the columns and their values in order
+    pub fn iter(&self)->IntoIter<(&str, &Type)>{
+        let entries:Vec<(&str, &Type)> = self.entries.iter().map(|e| (&e.0[..], &e.1)).collect();
+        entries.into_iter()
+    }
+
+    /// the column and its value at the position
+    pub fn get_at(&self, index:usize)->Option<(&str, &Type)>{
+        self.entries.get(index).map(|e| (&e.0[..], &e.1))
+    }
+
+    pub fn len(&self)->usize{
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self)->bool{
+        self.entries.is_empty()
+    }
+}
+
+impl <'a>IntoIterator for &'a Values{
+    type Item = (&'a str, &'a Type);
+    type IntoIter = IntoIter<(&'a str, &'a Type)>;
+
+    fn into_iter(self)->Self::IntoIter{
+        self.iter()
+    }
+}
+
+/// encoded as a map of the column to its value
+impl Encodable for Values{
+    fn encode<S: Encoder>(&self, s: &mut S)->Result<(), S::Error>{
+        s.emit_map(self.entries.len(), |s| {
+            for (i, &(ref column, ref value)) in self.entries.iter().enumerate(){
+                try!(s.emit_map_elt_key(i, |s| column.encode(s)));
+                try!(s.emit_map_elt_val(i, |s| value.encode(s)));
+            }
+            Ok(())
+        })
+    }
+}
+
+impl Decodable for Values{
+    fn decode<D: Decoder>(d: &mut D)->Result<Self, D::Error>{
+        d.read_map(|d, len| {
+            let mut values = Values::new();
+            for i in 0..len{
+                let column:String = try!(d.read_map_elt_key(i, |d| Decodable::decode(d)));
+                let value:Type = try!(d.read_map_elt_val(i, |d| Decodable::decode(d)));
+                values.insert(column, value);
+            }
+            Ok(values)
+        })
+    }
+}
+
 #[derive(RustcDecodable, RustcEncodable)]
 #[derive(Debug, Clone)]
 pub struct Dao{
-    pub values:HashMap<String, Type>,
+    pub values:Values,
 }
 
 impl Dao{
 
     pub fn new()->Self{
-        Dao{values:HashMap::new()}
+        Dao{values:Values::new()}
     }
     
     pub fn set(&mut self, column: &str, value:&ToType){
-        self.values.insert(column.to_string(), value.to_db_type());
+        self.set_value(column, value.to_db_type());
     }
     
+    /// setting an existing column keeps its position
     pub fn set_value(&mut self, column: &str, value:Type){
         self.values.insert(column.to_string(), value);
     }
@@ -401,8 +998,43 @@ impl Dao{
 
     /// take the value but not removing the content
     pub fn get<T>(&self, column: &str) -> T where T: FromType{
-        let value = self.values.get(column).unwrap();
-        FromType::from_type(value.clone())
+        match self.try_get(column){
+            Ok(value) => value,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// the value of the column, or an error when the column is missing or can not be converted
+    pub fn try_get<T>(&self, column: &str) -> Result<T, DaoError> where T: FromType{
+        match self.values.get(column){
+            Some(value) => T::try_from_type(value.clone())
+                .map_err(|e| DaoError::TypeMismatch{column: column.to_string(), message: e}),
+            None => Err(DaoError::MissingColumn(column.to_string())),
+        }
+    }
+
+    /// the columns and their values in the order they are set
+    pub fn iter(&self)->IntoIter<(&str, &Type)>{
+        self.values.iter()
+    }
+
+    /// set all the values of the other dao into this dao, overwriting the existing ones
+    pub fn merge(&mut self, other:&Dao){
+        for (column, value) in other.iter(){
+            self.set_value(column, value.clone());
+        }
+    }
+
+    /// the values of the other dao which are not in this dao or have a different value,
+    /// ie: the changes of a record to be updated
+    pub fn diff(&self, other:&Dao)->Dao{
+        let mut changed = Dao::new();
+        for (column, value) in other.iter(){
+            if self.values.get(column) != Some(value){
+                changed.set_value(column, value.clone());
+            }
+        }
+        changed
     }
     /// get optional value
     pub fn get_opt<T>(&self, column: &str) -> Option<T> where T: FromType{
@@ -425,23 +1057,61 @@ impl Dao{
     
     /// set the short column names using the renamed columns from the table specified
     /// will be used when casting a generic dao to multiple dao values, 
-    /// ie. useful when querying 1 time using 1:1 joins
-    /// TODO: deal with the optional columns
+    /// ie. useful when querying 1 time using 1:1 joins,
+    /// the renamed columns which are not in this dao are skipped
     pub fn resolve_renamed_columns(&mut self, in_table:&str, renamed_columns:&BTreeMap<String, Vec<(String, String)>>){
         let renamed_columns = renamed_columns.get(in_table);
         match renamed_columns{
             Some(renamed_columns)=>{
                 for c in renamed_columns{
                     let &(ref column, ref renamed) = c;
-                    println!("setting: {} to value: {}", renamed, column);
-                    let orig = self.values.get(renamed).unwrap().clone();
-                    self.values.insert(column.to_string(), orig);
+                    let orig = match self.values.get(renamed){
+                        Some(orig) => orig.clone(),
+                        None => continue,
+                    };
+                    self.set_value(column, orig);
                 }
              },
             None => (),
         };
     }
 
+    /// the number of columns
+    pub fn len(&self)->usize{
+        self.values.len()
+    }
+
+    pub fn is_empty(&self)->bool{
+        self.values.is_empty()
+    }
+
+    /// the column and its value at the position in the order of the select
+    pub fn get_at(&self, index:usize)->Option<(&str, &Type)>{
+        self.values.get_at(index)
+    }
+
+    /// the value of the column of the table, looked up by the table qualified name
+    /// which the columns of the joined tables are renamed to, then by the column name
+    pub fn get_in<T>(&self, table:&str, column:&str)->Option<T> where T: FromType{
+        let qualified = format!("{}.{}", table, column);
+        if self.values.contains_key(&qualified){
+            self.get_opt(&qualified)
+        }else{
+            self.get_opt(column)
+        }
+    }
+
+}
+
+/// `Dao::from(vec![("name", &"iphone" as &ToType), ("price", &100)])`
+impl <'a>From<Vec<(&'a str, &'a ToType)>> for Dao{
+    fn from(values:Vec<(&'a str, &'a ToType)>)->Self{
+        let mut dao = Dao::new();
+        for (column, value) in values{
+            dao.set(column, value);
+        }
+        dao
+    }
 }
 
 #[test]
@@ -462,3 +1132,100 @@ fn test_dao(){
     assert_eq!(date, created);
     assert_eq!(none, None);
 }
+
+#[test]
+fn test_json_value(){
+    use rustc_serialize::json;
+    let value = JsonValue(Json::from_str(r#"{"tags": ["phone", "apple"], "weight": 2}"#).unwrap());
+    let encoded = json::encode(&Type::Json(value.clone())).unwrap();
+    let decoded:Type = json::decode(&encoded).unwrap();
+    assert_eq!(decoded, Type::Json(value.clone()));
+    let mut d = Dao::new();
+    d.set("data", &value);
+    let data:Json = d.get("data");
+    assert_eq!(data["tags"][1], Json::String("apple".to_string()));
+}
+
+#[test]
+fn test_array(){
+    let mut d = Dao::new();
+    d.set("tags", &vec!["phone", "apple"]);
+    d.set("ratings", &vec![Some(5), None]);
+    d.set_value("image", Type::VecU8(vec![1, 2]));
+    assert_eq!(d.get_value("tags"), Type::Array(vec![Type::String("phone".to_string()), Type::String("apple".to_string())]));
+    let tags:Vec<String> = d.get("tags");
+    let ratings:Vec<Option<i32>> = d.get("ratings");
+    let image:Vec<u8> = d.get("image");
+    assert_eq!(tags, vec!["phone".to_string(), "apple".to_string()]);
+    assert_eq!(ratings, vec![Some(5), None]);
+    assert_eq!(image, vec![1, 2]);
+}
+
+#[test]
+fn test_range(){
+    let mut d = Dao::new();
+    d.set("stock", &Range::new(1, 10));
+    d.set("shipping", &Interval::from_seconds(3600));
+    assert_eq!(d.get_value("stock"), Type::Range(Box::new(Range::new(Type::I32(1), Type::I32(10)))));
+    let stock:Range<i32> = d.get("stock");
+    let shipping:Interval = d.get("shipping");
+    assert_eq!(stock, Range::Between(RangeBound::Inclusive(1), RangeBound::Exclusive(10)));
+    assert_eq!(shipping.microseconds, 3_600_000_000);
+}
+
+#[test]
+fn test_try_get(){
+    let mut d = Dao::from(vec![("name", &"iphone" as &ToType), ("stock", &10i16), ("weight", &1.5f32)]);
+    d.set("id", &1);
+    let stock:i64 = d.try_get("stock").unwrap();
+    let weight:f64 = d.try_get("weight").unwrap();
+    assert_eq!(stock, 10);
+    assert_eq!(weight, 1.5);
+    assert_eq!(d.try_get::<i32>("price"), Err(DaoError::MissingColumn("price".to_string())));
+    match d.try_get::<i32>("name"){
+        Err(DaoError::TypeMismatch{column, ..}) => assert_eq!(column, "name"),
+        _ => panic!("expecting a type mismatch"),
+    }
+    let columns:Vec<&str> = d.iter().map(|(c, _)| c).collect();
+    assert_eq!(columns, vec!["name", "stock", "weight", "id"]);
+
+    let mut changed = d.clone();
+    changed.set("stock", &9i16);
+    changed.set("price", &100);
+    let diff = d.diff(&changed);
+    let columns:Vec<&str> = diff.values.keys().collect();
+    assert_eq!(columns, vec!["stock", "price"]);
+    d.merge(&diff);
+    assert_eq!(d.get::<i16>("stock"), 9);
+    let columns:Vec<&str> = d.iter().map(|(c, _)| c).collect();
+    assert_eq!(columns, vec!["name", "stock", "weight", "id", "price"]);
+
+    use rustc_serialize::json;
+    let decoded:Dao = json::decode(&json::encode(&d).unwrap()).unwrap();
+    let columns:Vec<&str> = decoded.values.keys().collect();
+    assert_eq!(columns, vec!["name", "stock", "weight", "id", "price"]);
+}
+
+#[test]
+fn test_renamed_columns(){
+    let mut d = Dao::new();
+    d.set("product_id", &1);
+    d.set("name", &"iphone");
+    d.set("category.name", &"phone");
+    assert_eq!(d.len(), 3);
+    assert_eq!(d.get_at(2), Some(("category.name", &Type::String("phone".to_string()))));
+    assert_eq!(d.get_at(3), None);
+    d.values.insert("description".to_string(), Type::Null);
+    assert_eq!(d.len(), 4);
+    assert_eq!(d.get_at(3), Some(("description", &Type::Null)));
+    d.remove::<i32>("product_id");
+    assert_eq!(d.get_at(0), Some(("name", &Type::String("iphone".to_string()))));
+    assert!(!d.is_empty());
+    assert_eq!(d.get_in::<String>("category", "name"), Some("phone".to_string()));
+    assert_eq!(d.get_in::<String>("product", "name"), Some("iphone".to_string()));
+    let mut renamed = BTreeMap::new();
+    renamed.insert("category".to_string(), vec![("name".to_string(), "category.name".to_string()),
+                                                ("description".to_string(), "category.description".to_string())]);
+    d.resolve_renamed_columns("category", &renamed);
+    assert_eq!(d.get::<String>("name"), "phone");
+}
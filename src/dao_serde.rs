@@ -0,0 +1,550 @@
+//! Convert between Dao and any struct which implements serde `Deserialize` or `Serialize`,
+//! without implementing IsDao for each struct.
+//!
+//! ```rust,no_run
+//! #[derive(Deserialize)]
+//! struct ProductName{
+//!     name: String,
+//!     description: Option<String>,
+//! }
+//!
+//! let mut query = Query::select_all();
+//! query.from_table("bazaar.product");
+//! let products:Vec<ProductName> = query.collect_deserialized(&pg).unwrap();
+//! ```
+use dao::{Dao, Type};
+use table::Table;
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Visitor, MapAccess, SeqAccess, IntoDeserializer};
+use serde::ser::{self, Serialize, SerializeSeq, Impossible};
+use uuid::Uuid;
+use rustc_serialize::json::Json;
+use decimal::Decimal;
+use chrono::datetime::DateTime;
+use chrono::naive::date::NaiveDate;
+use chrono::naive::time::NaiveTime;
+use chrono::naive::datetime::NaiveDateTime;
+use chrono::offset::utc::UTC;
+use std::fmt;
+use std::error::Error;
+use std::slice::Iter;
+
+/// the errors when converting between Dao and structs
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum DaoError{
+    /// the struct has a field which is not in the Dao
+    MissingColumn(String),
+    /// the value of the column can not be converted into the type of the field
+    TypeMismatch{column: String, message: String},
+    /// the struct can not be represented as a Dao, ie: nested structs and sequences
+    Unsupported(String),
+    Custom(String),
+}
+
+impl DaoError{
+
+    /// put the column in the error that happened while converting its value
+    fn in_column(self, column: &str)->Self{
+        match self{
+            DaoError::Custom(message) => DaoError::TypeMismatch{column: column.to_string(), message: message},
+            e => e,
+        }
+    }
+}
+
+impl fmt::Display for DaoError{
+    fn fmt(&self, f: &mut fmt::Formatter)->fmt::Result{
+        match *self{
+            DaoError::MissingColumn(ref column) => write!(f, "There is no column {} in the record", column),
+            DaoError::TypeMismatch{ref column, ref message} => write!(f, "Column {}: {}", column, message),
+            DaoError::Unsupported(ref message) => write!(f, "Unsupported: {}", message),
+            DaoError::Custom(ref message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for DaoError{
+    fn description(&self)->&str{
+        match *self{
+            DaoError::MissingColumn(_) => "missing column",
+            DaoError::TypeMismatch{..} => "type mismatch",
+            DaoError::Unsupported(_) => "unsupported",
+            DaoError::Custom(ref message) => message,
+        }
+    }
+}
+
+impl de::Error for DaoError{
+    fn custom<T: fmt::Display>(msg: T)->Self{
+        DaoError::Custom(msg.to_string())
+    }
+
+    fn missing_field(field: &'static str)->Self{
+        DaoError::MissingColumn(field.to_string())
+    }
+}
+
+impl ser::Error for DaoError{
+    fn custom<T: fmt::Display>(msg: T)->Self{
+        DaoError::Custom(msg.to_string())
+    }
+}
+
+/// deserialize the record into the struct
+pub fn from_dao<T>(dao: &Dao)->Result<T, DaoError> where T: DeserializeOwned{
+    T::deserialize(DaoDeserializer{dao: dao})
+}
+
+/// serialize the struct into a record, the fields become the columns
+pub fn to_dao<T>(value: &T)->Result<Dao, DaoError> where T: Serialize{
+    value.serialize(DaoSerializer)
+}
+
+/// serialize the struct into a record of the table,
+/// string values are converted into the type of the column such as uuid and timestamp,
+/// float values into decimal for numeric columns
+pub fn to_table_dao<T>(value: &T, table: &Table)->Result<Dao, DaoError> where T: Serialize{
+    let mut dao = try!(to_dao(value));
+    for column in &table.columns{
+        let converted = match dao.values.get(&column.name){
+            Some(&Type::String(ref s)) => try!(parse_as(s, &column.data_type)
+                    .map_err(|e| e.in_column(&column.name))),
+            Some(&Type::F64(f)) => try!(parse_as(&f.to_string(), &column.data_type)
+                    .map_err(|e| e.in_column(&column.name))),
+            _ => None,
+        };
+        match converted{
+            Some(value) => dao.set_value(&column.name, value),
+            None => (),
+        }
+    }
+    Ok(dao)
+}
+
+/// parse the string as the data type, None when the data type is a string or is not supported
+fn parse_as(s: &str, data_type: &str)->Result<Option<Type>, DaoError>{
+    let value = match data_type{
+        "Uuid" => Uuid::parse_str(s).map(Type::Uuid).map_err(|e| format!("{:?}", e)),
+        "DateTime<UTC>" => DateTime::parse_from_rfc3339(s).map(|d| Type::DateTime(d.with_timezone(&UTC))).map_err(|e| e.to_string()),
+        "NaiveDate" => s.parse::<NaiveDate>().map(Type::NaiveDate).map_err(|e| e.to_string()),
+        "NaiveTime" => s.parse::<NaiveTime>().map(Type::NaiveTime).map_err(|e| e.to_string()),
+        "Decimal" => s.parse::<Decimal>().map(Type::Decimal),
+        "NaiveDateTime" => s.parse::<NaiveDateTime>().map(Type::NaiveDateTime).map_err(|e| e.to_string()),
+        _ => return Ok(None),
+    };
+    match value{
+        Ok(value) => Ok(Some(value)),
+        Err(e) => Err(DaoError::Custom(format!("Unable to parse {} as {} due to {}", s, data_type, e))),
+    }
+}
+
+/// deserializes a Dao as a struct or a map
+struct DaoDeserializer<'a>{
+    dao: &'a Dao,
+}
+
+impl <'de, 'a>de::Deserializer<'de> for DaoDeserializer<'a>{
+    type Error = DaoError;
+
+    fn deserialize_any<V>(self, visitor: V)->Result<V::Value, DaoError> where V: Visitor<'de>{
+        let entries = self.dao.iter().collect();
+        visitor.visit_map(DaoMapAccess{entries: entries, index: 0})
+    }
+
+    /// only the fields of the struct that are in the dao are visited,
+    /// the missing fields are None when optional, otherwise a MissingColumn error
+    fn deserialize_struct<V>(self, _name: &'static str, fields: &'static [&'static str], visitor: V)
+        ->Result<V::Value, DaoError> where V: Visitor<'de>{
+        let mut entries = vec![];
+        for field in fields{
+            match self.dao.values.get(*field){
+                Some(value) => entries.push((*field, value)),
+                None => (),
+            }
+        }
+        visitor.visit_map(DaoMapAccess{entries: entries, index: 0})
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+struct DaoMapAccess<'a>{
+    entries: Vec<(&'a str, &'a Type)>,
+    index: usize,
+}
+
+impl <'de, 'a>MapAccess<'de> for DaoMapAccess<'a>{
+    type Error = DaoError;
+
+    fn next_key_seed<K>(&mut self, seed: K)->Result<Option<K::Value>, DaoError> where K: DeserializeSeed<'de>{
+        if self.index >= self.entries.len(){
+            return Ok(None);
+        }
+        let column = self.entries[self.index].0;
+        seed.deserialize(column.into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V)->Result<V::Value, DaoError> where V: DeserializeSeed<'de>{
+        let (column, value) = self.entries[self.index];
+        self.index += 1;
+        seed.deserialize(TypeDeserializer{value: value}).map_err(|e| e.in_column(column))
+    }
+}
+
+/// deserializes the value of a column
+struct TypeDeserializer<'a>{
+    value: &'a Type,
+}
+
+impl <'de, 'a>de::Deserializer<'de> for TypeDeserializer<'a>{
+    type Error = DaoError;
+
+    fn deserialize_any<V>(self, visitor: V)->Result<V::Value, DaoError> where V: Visitor<'de>{
+        match *self.value{
+            Type::Bool(x) => visitor.visit_bool(x),
+            Type::I8(x) => visitor.visit_i8(x),
+            Type::I16(x) => visitor.visit_i16(x),
+            Type::I32(x) => visitor.visit_i32(x),
+            Type::I64(x) => visitor.visit_i64(x),
+            Type::U8(x) => visitor.visit_u8(x),
+            Type::U16(x) => visitor.visit_u16(x),
+            Type::U32(x) => visitor.visit_u32(x),
+            Type::U64(x) => visitor.visit_u64(x),
+            Type::F32(x) => visitor.visit_f32(x),
+            Type::F64(x) => visitor.visit_f64(x),
+            // exact, deserialize into a Decimal or a String field to keep the precision
+            Type::Decimal(ref x) => visitor.visit_string(x.to_string()),
+            Type::String(ref x) => visitor.visit_str(x),
+            Type::VecU8(ref x) => visitor.visit_bytes(x),
+            Type::Uuid(ref x) => visitor.visit_string(x.to_string()),
+            Type::DateTime(ref x) => visitor.visit_string(x.to_rfc3339()),
+            Type::NaiveDate(ref x) => visitor.visit_string(format!("{:?}", x)),
+            Type::NaiveTime(ref x) => visitor.visit_string(format!("{:?}", x)),
+            Type::NaiveDateTime(ref x) => visitor.visit_string(format!("{:?}", x)),
+            // scalars are visited as is, arrays and objects as the json text
+            Type::Json(ref x) => match x.0{
+                Json::Null => visitor.visit_unit(),
+                Json::Boolean(b) => visitor.visit_bool(b),
+                Json::I64(i) => visitor.visit_i64(i),
+                Json::U64(u) => visitor.visit_u64(u),
+                Json::F64(f) => visitor.visit_f64(f),
+                Json::String(ref s) => visitor.visit_str(s),
+                Json::Array(_) | Json::Object(_) => visitor.visit_string(x.to_string()),
+            },
+            Type::Array(ref x) => visitor.visit_seq(ArrayAccess{values: x.iter()}),
+            Type::Enum(ref x) => visitor.visit_str(x),
+            // the fields in order, deserialize into a tuple or a struct
+            Type::Composite(ref x) => visitor.visit_seq(ArrayAccess{values: x.iter()}),
+            Type::Inet(ref x) => visitor.visit_string(x.to_string()),
+            Type::MacAddr(ref x) => visitor.visit_string(x.to_string()),
+            Type::Money(ref x) => visitor.visit_i64(x.0),
+            Type::Interval(_) | Type::Point(_) | Type::Range(_) => {
+                Err(DaoError::Unsupported(format!("deserializing {:?}", self.value)))
+            },
+            Type::Map(_) => Err(DaoError::Unsupported(format!("deserializing a map column"))),
+            Type::Null => visitor.visit_unit(),
+        }
+    }
+
+    /// Null is None, everything else is Some
+    fn deserialize_option<V>(self, visitor: V)->Result<V::Value, DaoError> where V: Visitor<'de>{
+        match *self.value{
+            Type::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V)->Result<V::Value, DaoError>
+        where V: Visitor<'de>{
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// decimals can also be read into float fields, with the loss of precision
+    fn deserialize_f64<V>(self, visitor: V)->Result<V::Value, DaoError> where V: Visitor<'de>{
+        match *self.value{
+            Type::Decimal(ref x) => visitor.visit_f64(x.to_f64()),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_f32<V>(self, visitor: V)->Result<V::Value, DaoError> where V: Visitor<'de>{
+        self.deserialize_f64(visitor)
+    }
+
+    /// enum labels and strings are the unit variants of rust enums
+    fn deserialize_enum<V>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V)
+        ->Result<V::Value, DaoError> where V: Visitor<'de>{
+        match *self.value{
+            Type::Enum(ref x) | Type::String(ref x) => visitor.visit_enum(x.as_str().into_deserializer()),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 char str string bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// the elements of an array column
+struct ArrayAccess<'a>{
+    values: Iter<'a, Type>,
+}
+
+impl <'de, 'a>SeqAccess<'de> for ArrayAccess<'a>{
+    type Error = DaoError;
+
+    fn next_element_seed<T>(&mut self, seed: T)->Result<Option<T::Value>, DaoError> where T: DeserializeSeed<'de>{
+        match self.values.next(){
+            Some(value) => seed.deserialize(TypeDeserializer{value: value}).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// serializes a struct or a map into a Dao
+struct DaoSerializer;
+
+/// collects the fields of the struct or the entries of the map
+struct DaoFields{
+    dao: Dao,
+    /// the key of the map entry waiting for its value
+    key: Option<String>,
+}
+
+fn unsupported<T>(what: &str)->Result<T, DaoError>{
+    Err(DaoError::Unsupported(format!("{} can not be serialized as a record, only structs and maps can", what)))
+}
+
+impl ser::Serializer for DaoSerializer{
+    type Ok = Dao;
+    type Error = DaoError;
+    type SerializeSeq = Impossible<Dao, DaoError>;
+    type SerializeTuple = Impossible<Dao, DaoError>;
+    type SerializeTupleStruct = Impossible<Dao, DaoError>;
+    type SerializeTupleVariant = Impossible<Dao, DaoError>;
+    type SerializeMap = DaoFields;
+    type SerializeStruct = DaoFields;
+    type SerializeStructVariant = Impossible<Dao, DaoError>;
+
+    fn serialize_struct(self, _name: &'static str, _len: usize)->Result<DaoFields, DaoError>{
+        Ok(DaoFields{dao: Dao::new(), key: None})
+    }
+
+    fn serialize_map(self, _len: Option<usize>)->Result<DaoFields, DaoError>{
+        Ok(DaoFields{dao: Dao::new(), key: None})
+    }
+
+    fn serialize_newtype_struct<T: ?Sized>(self, _name: &'static str, value: &T)->Result<Dao, DaoError>
+        where T: Serialize{
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _v: bool)->Result<Dao, DaoError>{ unsupported("bool") }
+    fn serialize_i8(self, _v: i8)->Result<Dao, DaoError>{ unsupported("i8") }
+    fn serialize_i16(self, _v: i16)->Result<Dao, DaoError>{ unsupported("i16") }
+    fn serialize_i32(self, _v: i32)->Result<Dao, DaoError>{ unsupported("i32") }
+    fn serialize_i64(self, _v: i64)->Result<Dao, DaoError>{ unsupported("i64") }
+    fn serialize_u8(self, _v: u8)->Result<Dao, DaoError>{ unsupported("u8") }
+    fn serialize_u16(self, _v: u16)->Result<Dao, DaoError>{ unsupported("u16") }
+    fn serialize_u32(self, _v: u32)->Result<Dao, DaoError>{ unsupported("u32") }
+    fn serialize_u64(self, _v: u64)->Result<Dao, DaoError>{ unsupported("u64") }
+    fn serialize_f32(self, _v: f32)->Result<Dao, DaoError>{ unsupported("f32") }
+    fn serialize_f64(self, _v: f64)->Result<Dao, DaoError>{ unsupported("f64") }
+    fn serialize_char(self, _v: char)->Result<Dao, DaoError>{ unsupported("char") }
+    fn serialize_str(self, _v: &str)->Result<Dao, DaoError>{ unsupported("str") }
+    fn serialize_bytes(self, _v: &[u8])->Result<Dao, DaoError>{ unsupported("bytes") }
+    fn serialize_none(self)->Result<Dao, DaoError>{ unsupported("None") }
+    fn serialize_some<T: ?Sized>(self, value: &T)->Result<Dao, DaoError> where T: Serialize{
+        value.serialize(self)
+    }
+    fn serialize_unit(self)->Result<Dao, DaoError>{ unsupported("unit") }
+    fn serialize_unit_struct(self, name: &'static str)->Result<Dao, DaoError>{ unsupported(name) }
+    fn serialize_unit_variant(self, name: &'static str, _index: u32, _variant: &'static str)
+        ->Result<Dao, DaoError>{ unsupported(name) }
+    fn serialize_newtype_variant<T: ?Sized>(self, name: &'static str, _index: u32, _variant: &'static str, _value: &T)
+        ->Result<Dao, DaoError> where T: Serialize{ unsupported(name) }
+    fn serialize_seq(self, _len: Option<usize>)->Result<Self::SerializeSeq, DaoError>{ unsupported("sequence") }
+    fn serialize_tuple(self, _len: usize)->Result<Self::SerializeTuple, DaoError>{ unsupported("tuple") }
+    fn serialize_tuple_struct(self, name: &'static str, _len: usize)
+        ->Result<Self::SerializeTupleStruct, DaoError>{ unsupported(name) }
+    fn serialize_tuple_variant(self, name: &'static str, _index: u32, _variant: &'static str, _len: usize)
+        ->Result<Self::SerializeTupleVariant, DaoError>{ unsupported(name) }
+    fn serialize_struct_variant(self, name: &'static str, _index: u32, _variant: &'static str, _len: usize)
+        ->Result<Self::SerializeStructVariant, DaoError>{ unsupported(name) }
+}
+
+impl ser::SerializeStruct for DaoFields{
+    type Ok = Dao;
+    type Error = DaoError;
+
+    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T)->Result<(), DaoError>
+        where T: Serialize{
+        let value = try!(value.serialize(TypeSerializer).map_err(|e| e.in_column(key)));
+        self.dao.set_value(key, value);
+        Ok(())
+    }
+
+    fn end(self)->Result<Dao, DaoError>{
+        Ok(self.dao)
+    }
+}
+
+impl ser::SerializeMap for DaoFields{
+    type Ok = Dao;
+    type Error = DaoError;
+
+    fn serialize_key<T: ?Sized>(&mut self, key: &T)->Result<(), DaoError> where T: Serialize{
+        match try!(key.serialize(TypeSerializer)){
+            Type::String(key) => {
+                self.key = Some(key);
+                Ok(())
+            },
+            key => Err(DaoError::Unsupported(format!("the key {:?} should be a string", key))),
+        }
+    }
+
+    fn serialize_value<T: ?Sized>(&mut self, value: &T)->Result<(), DaoError> where T: Serialize{
+        let key = self.key.take().expect("serialize_key should be called before serialize_value");
+        let value = try!(value.serialize(TypeSerializer).map_err(|e| e.in_column(&key)));
+        self.dao.set_value(&key, value);
+        Ok(())
+    }
+
+    fn end(self)->Result<Dao, DaoError>{
+        Ok(self.dao)
+    }
+}
+
+/// serializes the value of a field into the Type of a column
+struct TypeSerializer;
+
+impl ser::Serializer for TypeSerializer{
+    type Ok = Type;
+    type Error = DaoError;
+    type SerializeSeq = ArraySerializer;
+    type SerializeTuple = Impossible<Type, DaoError>;
+    type SerializeTupleStruct = Impossible<Type, DaoError>;
+    type SerializeTupleVariant = Impossible<Type, DaoError>;
+    type SerializeMap = Impossible<Type, DaoError>;
+    type SerializeStruct = Impossible<Type, DaoError>;
+    type SerializeStructVariant = Impossible<Type, DaoError>;
+
+    fn serialize_bool(self, v: bool)->Result<Type, DaoError>{ Ok(Type::Bool(v)) }
+    fn serialize_i8(self, v: i8)->Result<Type, DaoError>{ Ok(Type::I8(v)) }
+    fn serialize_i16(self, v: i16)->Result<Type, DaoError>{ Ok(Type::I16(v)) }
+    fn serialize_i32(self, v: i32)->Result<Type, DaoError>{ Ok(Type::I32(v)) }
+    fn serialize_i64(self, v: i64)->Result<Type, DaoError>{ Ok(Type::I64(v)) }
+    fn serialize_u8(self, v: u8)->Result<Type, DaoError>{ Ok(Type::U8(v)) }
+    fn serialize_u16(self, v: u16)->Result<Type, DaoError>{ Ok(Type::U16(v)) }
+    fn serialize_u32(self, v: u32)->Result<Type, DaoError>{ Ok(Type::U32(v)) }
+    fn serialize_u64(self, v: u64)->Result<Type, DaoError>{ Ok(Type::U64(v)) }
+    fn serialize_f32(self, v: f32)->Result<Type, DaoError>{ Ok(Type::F32(v)) }
+    fn serialize_f64(self, v: f64)->Result<Type, DaoError>{ Ok(Type::F64(v)) }
+    fn serialize_char(self, v: char)->Result<Type, DaoError>{ Ok(Type::String(v.to_string())) }
+    fn serialize_str(self, v: &str)->Result<Type, DaoError>{ Ok(Type::String(v.to_string())) }
+    fn serialize_bytes(self, v: &[u8])->Result<Type, DaoError>{ Ok(Type::VecU8(v.to_vec())) }
+    fn serialize_none(self)->Result<Type, DaoError>{ Ok(Type::Null) }
+    fn serialize_some<T: ?Sized>(self, value: &T)->Result<Type, DaoError> where T: Serialize{
+        value.serialize(self)
+    }
+    fn serialize_unit(self)->Result<Type, DaoError>{ Ok(Type::Null) }
+    fn serialize_unit_struct(self, _name: &'static str)->Result<Type, DaoError>{ Ok(Type::Null) }
+    /// enums without data are stored as the name of the variant
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str)
+        ->Result<Type, DaoError>{ Ok(Type::String(variant.to_string())) }
+    fn serialize_newtype_struct<T: ?Sized>(self, _name: &'static str, value: &T)->Result<Type, DaoError>
+        where T: Serialize{
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: ?Sized>(self, name: &'static str, _index: u32, _variant: &'static str, _value: &T)
+        ->Result<Type, DaoError> where T: Serialize{ unsupported(name) }
+    /// sequences are array columns
+    fn serialize_seq(self, len: Option<usize>)->Result<ArraySerializer, DaoError>{
+        Ok(ArraySerializer{values: Vec::with_capacity(len.unwrap_or(0))})
+    }
+    fn serialize_tuple(self, _len: usize)->Result<Self::SerializeTuple, DaoError>{ unsupported("tuple") }
+    fn serialize_tuple_struct(self, name: &'static str, _len: usize)
+        ->Result<Self::SerializeTupleStruct, DaoError>{ unsupported(name) }
+    fn serialize_tuple_variant(self, name: &'static str, _index: u32, _variant: &'static str, _len: usize)
+        ->Result<Self::SerializeTupleVariant, DaoError>{ unsupported(name) }
+    fn serialize_map(self, _len: Option<usize>)->Result<Self::SerializeMap, DaoError>{ unsupported("nested map") }
+    fn serialize_struct(self, name: &'static str, _len: usize)->Result<Self::SerializeStruct, DaoError>{
+        unsupported(name)
+    }
+    fn serialize_struct_variant(self, name: &'static str, _index: u32, _variant: &'static str, _len: usize)
+        ->Result<Self::SerializeStructVariant, DaoError>{ unsupported(name) }
+}
+
+/// collects the elements of an array column
+struct ArraySerializer{
+    values: Vec<Type>,
+}
+
+impl SerializeSeq for ArraySerializer{
+    type Ok = Type;
+    type Error = DaoError;
+
+    fn serialize_element<T: ?Sized>(&mut self, value: &T)->Result<(), DaoError> where T: Serialize{
+        self.values.push(try!(value.serialize(TypeSerializer)));
+        Ok(())
+    }
+
+    fn end(self)->Result<Type, DaoError>{
+        Ok(Type::Array(self.values))
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Product{
+    product_id: String,
+    name: String,
+    price: Option<f64>,
+    seq_no: i64,
+    tags: Vec<String>,
+}
+
+#[test]
+fn test_from_dao(){
+    let id = Uuid::new_v4();
+    let mut dao = Dao::new();
+    dao.set("product_id", &id);
+    dao.set("name", &"iphone");
+    dao.set_value("price", Type::Null);
+    dao.set("seq_no", &1i32);
+    dao.set("not_in_struct", &true);
+    dao.set("tags", &vec!["phone"]);
+    let product:Product = from_dao(&dao).unwrap();
+    assert_eq!(product, Product{product_id: id.to_string(), name: "iphone".to_string(), price: None, seq_no: 1, tags: vec!["phone".to_string()]});
+}
+
+#[test]
+fn test_from_dao_errors(){
+    let mut dao = Dao::new();
+    dao.set("product_id", &Uuid::new_v4());
+    dao.set("seq_no", &1);
+    dao.set("tags", &Vec::<String>::new());
+    assert_eq!(from_dao::<Product>(&dao), Err(DaoError::MissingColumn("name".to_string())));
+    dao.set("name", &10);
+    match from_dao::<Product>(&dao){
+        Err(DaoError::TypeMismatch{column, ..}) => assert_eq!(column, "name"),
+        other => panic!("expecting a type mismatch, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_to_dao(){
+    let id = Uuid::new_v4().to_string();
+    let product = Product{product_id: id.clone(), name: "iphone".to_string(), price: None, seq_no: 1, tags: vec![]};
+    let dao = to_dao(&product).unwrap();
+    assert_eq!(dao.get_value("product_id"), Type::String(id));
+    assert_eq!(dao.get_value("price"), Type::Null);
+    assert_eq!(dao.get_value("seq_no"), Type::I64(1));
+    assert_eq!(from_dao::<Product>(&dao).unwrap(), product);
+    assert!(to_dao(&1).is_err());
+}
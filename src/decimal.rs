@@ -0,0 +1,187 @@
+//! An exact decimal number for numeric columns,
+//! prices and totals can not be represented exactly with f64
+use rustc_serialize::{Encodable, Encoder, Decodable, Decoder};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::{self, Visitor};
+use std::fmt;
+use std::str::FromStr;
+
+/// The value is the digits as an integer, divided by 10 to the power of scale,
+/// ie: 12.50 is the digits 1250 with a scale of 2.
+/// The scale is kept as is, so 12.5 and 12.50 are not equal, the same as the text representation in postgresql
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub struct Decimal{
+    negative: bool,
+    /// the decimal digits 0..9 without the leading zeroes, empty when zero
+    digits: Vec<u8>,
+    /// the number of digits after the decimal point
+    scale: u16,
+}
+
+impl Decimal{
+
+    /// the number of digits after the decimal point
+    pub fn scale(&self)->u16{
+        self.scale
+    }
+
+    /// the total number of significant digits, at least the scale
+    pub fn precision(&self)->u16{
+        let len = self.digits.len() as u16;
+        if len > self.scale{ len }else{ self.scale }
+    }
+
+    pub fn is_negative(&self)->bool{
+        self.negative
+    }
+
+    /// the digits before the decimal point, "0" when there is none
+    pub fn integer_digits(&self)->String{
+        let scale = self.scale as usize;
+        if self.digits.len() <= scale{
+            return "0".to_string();
+        }
+        self.digits[..self.digits.len() - scale].iter().map(|d| (b'0' + d) as char).collect()
+    }
+
+    /// the digits after the decimal point, padded with zeroes up to the scale
+    pub fn fraction_digits(&self)->String{
+        let scale = self.scale as usize;
+        let mut fraction = String::new();
+        for _ in self.digits.len()..scale{
+            fraction.push('0');
+        }
+        let start = if self.digits.len() > scale{ self.digits.len() - scale }else{ 0 };
+        for d in &self.digits[start..]{
+            fraction.push((b'0' + d) as char);
+        }
+        fraction
+    }
+
+    /// the nearest f64, which may lose precision
+    pub fn to_f64(&self)->f64{
+        self.to_string().parse().unwrap()
+    }
+}
+
+impl FromStr for Decimal{
+    type Err = String;
+
+    /// parse `[+-]digits[.digits]`
+    fn from_str(s: &str)->Result<Self, String>{
+        let text = s.trim();
+        let (negative, unsigned) = if text.starts_with("-"){
+            (true, &text[1..])
+        }else if text.starts_with("+"){
+            (false, &text[1..])
+        }else{
+            (false, text)
+        };
+        let (integer, fraction) = match unsigned.find('.'){
+            Some(i) => (&unsigned[..i], &unsigned[i + 1..]),
+            None => (unsigned, ""),
+        };
+        if integer.is_empty() && fraction.is_empty(){
+            return Err(format!("Invalid decimal {}", s));
+        }
+        let mut digits = vec![];
+        for c in integer.chars().chain(fraction.chars()){
+            match c.to_digit(10){
+                Some(d) => {
+                    if !(digits.is_empty() && d == 0){
+                        digits.push(d as u8);
+                    }
+                },
+                None => return Err(format!("Invalid decimal {}", s)),
+            }
+        }
+        Ok(Decimal{
+            negative: negative && !digits.is_empty(),
+            digits: digits,
+            scale: fraction.len() as u16,
+        })
+    }
+}
+
+impl fmt::Display for Decimal{
+    fn fmt(&self, f: &mut fmt::Formatter)->fmt::Result{
+        if self.negative{
+            try!(write!(f, "-"));
+        }
+        try!(write!(f, "{}", self.integer_digits()));
+        if self.scale > 0{
+            try!(write!(f, ".{}", self.fraction_digits()));
+        }
+        Ok(())
+    }
+}
+
+impl From<i64> for Decimal{
+    fn from(value: i64)->Self{
+        Decimal::from_str(&value.to_string()).unwrap()
+    }
+}
+
+/// encoded as the text, so no precision is lost
+impl Encodable for Decimal{
+    fn encode<S: Encoder>(&self, s: &mut S)->Result<(), S::Error>{
+        s.emit_str(&self.to_string())
+    }
+}
+
+impl Decodable for Decimal{
+    fn decode<D: Decoder>(d: &mut D)->Result<Self, D::Error>{
+        let text = try!(d.read_str());
+        Decimal::from_str(&text).map_err(|e| d.error(&e))
+    }
+}
+
+impl Serialize for Decimal{
+    fn serialize<S: Serializer>(&self, serializer: S)->Result<S::Ok, S::Error>{
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+struct DecimalVisitor;
+
+impl <'de>Visitor<'de> for DecimalVisitor{
+    type Value = Decimal;
+
+    fn expecting(&self, f: &mut fmt::Formatter)->fmt::Result{
+        write!(f, "a decimal number as a string or an integer")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str)->Result<Decimal, E>{
+        Decimal::from_str(v).map_err(E::custom)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64)->Result<Decimal, E>{
+        Ok(Decimal::from(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64)->Result<Decimal, E>{
+        Decimal::from_str(&v.to_string()).map_err(E::custom)
+    }
+}
+
+impl <'de>Deserialize<'de> for Decimal{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D)->Result<Decimal, D::Error>{
+        deserializer.deserialize_any(DecimalVisitor)
+    }
+}
+
+#[test]
+fn test_decimal(){
+    let price = Decimal::from_str("-0012.050").unwrap();
+    assert_eq!(price.to_string(), "-12.050");
+    assert_eq!(price.scale(), 3);
+    assert_eq!(price.precision(), 5);
+    assert_eq!(Decimal::from_str(".5").unwrap().to_string(), "0.5");
+    assert_eq!(Decimal::from_str("0.001").unwrap().fraction_digits(), "001");
+    assert_eq!(Decimal::from_str("-0.00").unwrap().to_string(), "0.00");
+    assert_eq!(Decimal::from(42).to_string(), "42");
+    assert!(Decimal::from_str("1.2.3").is_err());
+    assert!(Decimal::from_str("-").is_err());
+}
@@ -1,23 +1,87 @@
 use query::{Filter,Operand};
 use query::Query;
-use table::Table;
+use table::{Table, Column};
 use dao::{Dao};
 use database::Database;
 use table::IsTable;
 use dao::IsDao;
 use dao::ToType;
 use query::Equality;
+use dao::{Type, HasRelation};
+use table::RefTable;
+use std::collections::BTreeMap;
+use std::cell::RefCell;
+use session::Session;
+use dao_serde::{self, DaoError};
+use serde::Serialize;
 
 /// A higher level API for manipulating objects in the database
 pub struct EntityManager<'a>{
     pub db:&'a Database,
+    /// records retrieved by this entity manager,
+    /// keyed by the complete table name and the primary key value
+    identity_map: RefCell<BTreeMap<(String, String), Dao>>,
+    /// lazily loaded relations, keyed by the complete table name, primary key value and the member name
+    relations: RefCell<BTreeMap<(String, String, String), Vec<Dao>>>,
 }
 
 impl <'a>EntityManager<'a>{
 
     /// Create an entity manager with the database connection provided
     pub fn new(db:&'a Database)->Self{
-        EntityManager{db:db}
+        EntityManager{
+            db:db, 
+            identity_map: RefCell::new(BTreeMap::new()),
+            relations: RefCell::new(BTreeMap::new()),
+        }
+    }
+    
+    /// start a session which keeps track of the changes of the entities,
+    /// the changes are written to the database in 1 transaction when flushed,
+    /// the records of the written tables are then evicted from this entity manager
+    pub fn session(&self)->Session{
+        Session::with_entity_manager(self)
+    }
+    
+    /// forget all the records and relations cached in this entity manager
+    pub fn clear(&self){
+        self.identity_map.borrow_mut().clear();
+        self.relations.borrow_mut().clear();
+    }
+    
+    /// the key of the record in the identity map,
+    /// integer ids are widened so `I32(1)` and `I64(1)` refer to the same record
+    fn identity_key(table:&Table, id:&Type)->(String, String){
+        (table.complete_name(), format!("{:?}", id.key_value()))
+    }
+    
+    /// put the record in the identity map, only tables with 1 primary column are mapped
+    fn register(&self, table:&Table, dao:&Dao){
+        let primary = table.primary_columns();
+        if primary.len() == 1{
+            match dao.values.get(&primary[0].name){
+                Some(id) => {
+                    let key = Self::identity_key(table, id);
+                    self.identity_map.borrow_mut().insert(key, dao.clone());
+                },
+                None => (),
+            }
+        }
+    }
+    
+    /// forget the records of the table, called whenever records of the table are written,
+    /// all the relations are forgotten as well since any of them may hold records of the table
+    pub fn evict_table(&self, table:&Table){
+        let table_name = table.complete_name();
+        let mut identity_map = self.identity_map.borrow_mut();
+        let keys:Vec<(String, String)> = identity_map.keys()
+            .filter(|k| k.0 == table_name)
+            .cloned()
+            .collect();
+        for k in keys{
+            identity_map.remove(&k);
+        }
+        self.relations.borrow_mut().clear();
     }
 
     /// create a database table aligned to this table definition
@@ -32,14 +96,44 @@ impl <'a>EntityManager<'a>{
     /// delete records of this table
     pub fn delete(&self, table:&Table, filters:&Vec<Filter>)->usize{
         let mut query = Query::delete();
+        query.from(table);
         for filter in filters{
             let f = filter.clone();
             query.add_filter(f);
         }
-        match query.execute(self.db){
+        let deleted = match self.db.delete(&query){
             Ok(x) => x,
             Err(e) => panic!("Error deleting record {}",e),
+        };
+        self.evict_table(table);
+        deleted
+    }
+    
+    /// delete the record matching the primary key of the table
+    pub fn delete_by_id<T>(&self, id: &ToType)->usize
+        where T : IsTable{
+        let table = T::table();
+        let primary = table.primary_columns();
+        assert!(primary.len() == 1, "There should only be 1 primary column for this to work");
+        let pk = primary[0].name.to_string();
+        let filter = Filter::new(&pk, Equality::EQ, id);
+        self.delete(&table, &vec![filter])
+    }
+    
+    /// delete records of this table, returning the deleted records
+    pub fn delete_with_return<T>(&self, filters:&Vec<Filter>)->Vec<T>
+        where T : IsTable + IsDao{
+        let table = T::table();
+        let mut query = Query::delete();
+        query.from(&table);
+        for filter in filters{
+            let f = filter.clone();
+            query.add_filter(f);
         }
+        query.enumerate_all_table_column_as_return(&table);
+        let deleted = query.collect(self.db);
+        self.evict_table(&table);
+        deleted
     }
 
     /// drop the database table
@@ -97,6 +191,177 @@ impl <'a>EntityManager<'a>{
         q.collect(self.db)
     }
 
+    
+    /// get all the records of this table, eagerly loading the referenced tables
+    /// relations are the member names of the referenced tables, ie: owner, availability, category
+    pub fn get_all_with_relations<T>(&self, relations:Vec<&str>, all_tables:&Vec<Table>)->Result<Vec<T>, String>
+        where T : IsTable + IsDao + HasRelation{
+        let table = T::table();
+        let mut q = Query::select();
+        q.enumerate_table_all_columns(&table);
+        q.from(&table);
+        self.collect_with_relations(&mut q, relations, all_tables)
+    }
+    
+    /// execute the query, then eagerly load the relations of the retrieved records
+    /// each relation is loaded using only 1 query with `IN` of the keys of all the records
+    /// the query should enumerate the key columns used in the relations
+    pub fn collect_with_relations<T>(&self, query:&mut Query, relations:Vec<&str>, all_tables:&Vec<Table>)->Result<Vec<T>, String>
+        where T : IsTable + IsDao + HasRelation{
+        let table = T::table();
+        let root = Table::get_table(&table.schema, &table.name, all_tables);
+        let ref_tables = root.get_all_referenced_table(all_tables);
+        let mut matched = vec![];
+        for relation in relations{
+            let ref_table = try!(Self::find_relation(root, relation, &ref_tables));
+            matched.push((relation, ref_table));
+        }
+        let result = query.execute_with_return(self.db);
+        let mut objs = T::from_dao_result(&result);
+        for (relation, ref_table) in matched{
+            let related = try!(self.load_relation(root, ref_table, &result.dao));
+            for (obj, records) in objs.iter_mut().zip(related.iter()){
+                obj.set_relation(relation, records);
+            }
+        }
+        Ok(objs)
+    }
+    
+    /// the referenced table of the root table which has the member name
+    fn find_relation<'r, 't>(root:&Table, relation:&str, ref_tables:&'r Vec<RefTable<'t>>)->Result<&'r RefTable<'t>, String>{
+        for ref_table in ref_tables{
+            if ref_table.member_name(root) == relation{
+                return Ok(ref_table);
+            }
+        }
+        Err(format!("{} has no relation named {}", root.complete_name(), relation))
+    }
+    
+    /// retrieve the records of the referenced table for all the daos in 1 query,
+    /// the records are then grouped in the same order of the daos
+    fn load_relation(&self, root:&Table, ref_table:&RefTable, daos:&Vec<Dao>)->Result<Vec<Vec<Dao>>, String>{
+        let (root_key, related_key, mut query) = Self::build_relation_query(root, ref_table);
+        let mut keys:Vec<Type> = vec![];
+        for dao in daos{
+            match dao.values.get(&root_key){
+                Some(&Type::Null) => (),
+                Some(value) => {
+                    let key = value.key_value();
+                    if !keys.iter().any(|k| k.key_value() == key){
+                        keys.push(value.clone());
+                    }
+                },
+                None => return Err(format!("The records have no column {}, it should be enumerated in the query", root_key)),
+            }
+        }
+        let mut records = vec![];
+        if !keys.is_empty(){
+            let filter_column = match ref_table.linker_table{
+                Some(linker) => format!("{}.{}", linker.name, Self::linker_column(linker, root).name),
+                None => related_key.to_string(),
+            };
+            query.add_filter(Filter::in_values(&filter_column, keys));
+            records = query.execute_with_return(self.db).dao;
+        }
+        Self::group_related(daos, &root_key, records, &related_key)
+    }
+    
+    /// group the related records by the value of their key column, in the same order of the daos,
+    /// the keys are compared by value so an int4 foreign key matches an int8 primary key
+    fn group_related(daos:&Vec<Dao>, root_key:&str, records:Vec<Dao>, related_key:&str)->Result<Vec<Vec<Dao>>, String>{
+        let mut grouped:Vec<(Type, Vec<Dao>)> = vec![];
+        for dao in records{
+            let key = match dao.values.get(related_key){
+                Some(value) => value.key_value(),
+                None => return Err(format!("The related records have no column {}", related_key)),
+            };
+            match grouped.iter().position(|&(ref k, _)| *k == key){
+                Some(index) => grouped[index].1.push(dao),
+                None => grouped.push((key, vec![dao])),
+            }
+        }
+        let mut related = vec![];
+        for dao in daos{
+            let records = match dao.values.get(root_key){
+                Some(&Type::Null) | None => vec![],
+                Some(value) => {
+                    let key = value.key_value();
+                    match grouped.iter().find(|&&(ref k, _)| *k == key){
+                        Some(&(_, ref records)) => records.clone(),
+                        None => vec![],
+                    }
+                },
+            };
+            related.push(records);
+        }
+        Ok(related)
+    }
+    
+    /// build the query to retrieve the referenced table,
+    /// returns the key column of the root table, the key column of the related records
+    /// and the query without the key filter
+    fn build_relation_query(root:&Table, ref_table:&RefTable)->(String, String, Query){
+        let table = ref_table.table;
+        let mut query = Query::select();
+        query.from(table);
+        query.enumerate_table_all_columns(table);
+        if ref_table.is_has_one{
+            let column = ref_table.column.unwrap();
+            let foreign = column.foreign.as_ref().unwrap();
+            return (column.name.to_string(), foreign.column.to_string(), query);
+        }
+        if ref_table.is_ext{
+            let root_pk = Self::single_primary(root);
+            for c in table.primary_columns(){
+                if c.foreign.is_some(){
+                    let foreign = c.foreign.as_ref().unwrap();
+                    if foreign.table == root.name && foreign.schema == root.schema{
+                        return (root_pk, c.name.to_string(), query);
+                    }
+                }
+            }
+            panic!("{} has no column referring to {}", table.complete_name(), root.complete_name());
+        }
+        if ref_table.is_has_many && ref_table.is_direct{
+            let column = ref_table.column.unwrap();
+            let foreign = column.foreign.as_ref().unwrap();
+            return (foreign.column.to_string(), column.name.to_string(), query);
+        }
+        // has many indirect, join the linker table to the other table
+        let linker = ref_table.linker_table.unwrap();
+        let to_root = Self::linker_column(linker, root);
+        let mut to_other = None;
+        for c in linker.foreign_columns(){
+            if c != to_root{
+                to_other = Some(c);
+            }
+        }
+        let to_other = to_other.unwrap();
+        let other_column = format!("{}.{}", table.name, to_other.foreign.as_ref().unwrap().column);
+        let linker_column = format!("{}.{}", linker.name, to_other.name);
+        query.inner_join(linker, &other_column, &linker_column);
+        let root_key_alias = format!("{}_{}", linker.name, to_root.name);
+        query.enumerate_column_as(&format!("{}.{}", linker.name, to_root.name), &root_key_alias);
+        (Self::single_primary(root), root_key_alias, query)
+    }
+    
+    /// the column of the linker table which refers to the root table
+    fn linker_column<'t>(linker:&'t Table, root:&Table)->&'t Column{
+        for c in linker.foreign_columns(){
+            let foreign = c.foreign.as_ref().unwrap();
+            if foreign.table == root.name && foreign.schema == root.schema{
+                return c;
+            }
+        }
+        panic!("{} has no column referring to {}", linker.complete_name(), root.complete_name());
+    }
+    
+    /// the name of the only primary column of the table
+    fn single_primary(table:&Table)->String{
+        let primary = table.primary_columns();
+        assert!(primary.len() == 1, "There should only be 1 primary column for this to work");
+        primary[0].name.to_string()
+    }
 
     /// get all the distinct records of this table
     pub fn get_all_distinct(&self, table:&Table)->Vec<Dao>{
@@ -154,18 +419,102 @@ impl <'a>EntityManager<'a>{
 /// }
 /// ```
 /// 
+    /// the record is retrieved only once, subsequent calls are taken from the identity map
+    /// of this entity manager
     pub fn get_exact<T>(&self, id: &ToType)->T 
         where T : IsTable + IsDao{
         let table = T::table();
-        let primary = table.primary_columns();
-        assert!(primary.len() == 1, "There should only be 1 primary column for this to work");
-        let pk = primary[0].name.to_string();
-        
+        match self.get_exact_dao(&table, id){
+            Ok(Some(dao)) => T::from_dao(&dao),
+            Ok(None) => panic!("{}", Self::not_found(&table, id)),
+            Err(e) => panic!("{}", e),
+        }
+    }
+    
+    /// get the record matching the primary key, from the identity map if already retrieved,
+    /// None when there is no such record, which is then not kept in the identity map
+    fn get_exact_dao(&self, table:&Table, id: &ToType)->Result<Option<Dao>, String>{
+        let key = Self::identity_key(table, &id.to_db_type());
+        match self.identity_map.borrow().get(&key){
+            Some(dao) => return Ok(Some(dao.clone())),
+            None => (),
+        };
+        let pk = Self::single_primary(table);
         let mut q = Query::select();
-        q.from(&table);
-        q.enumerate_table_all_columns(&table);
+        q.from(table);
+        q.enumerate_table_all_columns(table);
         q.filter(&pk, Equality::EQ, id);
-        q.collect_one(self.db)
+        q.limit(1);
+        q.finalize();
+        let sql_frag = self.db.build_query(&q);
+        let mut found = try!(self.db.try_execute_sql_with_return(&sql_frag.sql, &sql_frag.params));
+        if found.is_empty(){
+            return Ok(None);
+        }
+        let dao = found.remove(0);
+        self.identity_map.borrow_mut().insert(key, dao.clone());
+        Ok(Some(dao))
+    }
+
+    fn not_found(table:&Table, id: &ToType)->String{
+        format!("There is no record of {} with primary key {}", table.complete_name(), id.to_db_type())
+    }
+    
+    /// get the records of the referenced table of the record matching the primary key,
+    /// the relation is retrieved only the first time it is accessed
+    /// relation is the member name of the referenced table, ie: owner, availability, category
+    /// # Examples
+    /// ```rust,no_run
+    /// let em = EntityManager::new(&pg);
+    /// let tables = gen::get_all_tables();
+    /// let owner:Option<Users> = em.get_relation_one::<Product, Users>(&pid, "owner", &tables).unwrap();
+    /// let owner = owner.unwrap();
+    /// let org:Option<Organization> = em.get_relation_one::<Users, Organization>(&owner.user_id, "organization", &tables).unwrap();
+    /// ```
+    /// an error is returned when there is no such relation, or no record matching the primary key
+    pub fn get_relation<T, R>(&self, id: &ToType, relation:&str, all_tables:&Vec<Table>)->Result<Vec<R>, String>
+        where T : IsTable + IsDao, R : IsTable + IsDao{
+        let table = T::table();
+        let root = Table::get_table(&table.schema, &table.name, all_tables);
+        let (table_name, id_key) = Self::identity_key(root, &id.to_db_type());
+        let key = (table_name, id_key, relation.to_string());
+        match self.relations.borrow().get(&key){
+            Some(daos) => {
+                let mut objs = vec![];
+                for dao in daos{
+                    objs.push(R::from_dao(dao));
+                }
+                return Ok(objs);
+            },
+            None => (),
+        };
+        let ref_tables = root.get_all_referenced_table(all_tables);
+        let ref_table = try!(Self::find_relation(root, relation, &ref_tables));
+        let root_dao = match try!(self.get_exact_dao(root, id)){
+            Some(root_dao) => root_dao,
+            None => return Err(Self::not_found(root, id)),
+        };
+        let mut related = try!(self.load_relation(root, ref_table, &vec![root_dao]));
+        let daos = related.remove(0);
+        let mut objs = vec![];
+        for dao in &daos{
+            self.register(ref_table.table, dao);
+            objs.push(R::from_dao(dao));
+        }
+        self.relations.borrow_mut().insert(key, daos);
+        Ok(objs)
+    }
+    
+    /// get the has one or extension record of the referenced table,
+    /// the relation is retrieved only the first time it is accessed
+    pub fn get_relation_one<T, R>(&self, id: &ToType, relation:&str, all_tables:&Vec<Table>)->Result<Option<R>, String>
+        where T : IsTable + IsDao, R : IsTable + IsDao{
+        let mut objs:Vec<R> = try!(self.get_relation::<T, R>(id, relation, all_tables));
+        if objs.is_empty(){
+            Ok(None)
+        }else{
+            Ok(Some(objs.remove(0)))
+        }
     }
 
     /// insert this records to the database, return the inserted dao with
@@ -216,7 +565,32 @@ impl <'a>EntityManager<'a>{
                 None => (),
             };
         }
-        q.collect_one(self.db)
+        let inserted = q.collect_one(self.db);
+        self.evict_table(&table);
+        inserted
+    }
+
+    /// insert any struct which implements serde `Serialize`, the fields are the columns of the table,
+    /// fields which are not columns of the table are ignored,
+    /// returns the inserted record
+    pub fn insert_serialized<T>(&self, table:&Table, value:&T)->Result<Dao, DaoError>
+        where T: Serialize{
+        let dao = try!(dao_serde::to_table_dao(value, table));
+        let mut q = Query::insert();
+        q.into_table(table);
+        for c in &table.columns{
+            match dao.values.get(&c.name){
+                Some(value) => {
+                    q.enumerate_column(&c.name);
+                    q.add_value(Operand::Value(value.clone()));
+                }
+                None => (),
+            };
+        }
+        q.enumerate_all_table_column_as_return(table);
+        let inserted = q.execute_with_one_return(self.db);
+        self.evict_table(table);
+        Ok(inserted)
     }
 
     /// insert this record on the database, ignoring some columns
@@ -241,7 +615,9 @@ impl <'a>EntityManager<'a>{
                 None => (),
             };
         }
-        q.collect_one(self.db)
+        let inserted = q.collect_one(self.db);
+        self.evict_table(&table);
+        inserted
     }
 
     /// insert this record on the database, explicitly setting the defaults of the columns
@@ -313,3 +689,52 @@ impl <'a>EntityManager<'a>{
     }
 
 }
+
+#[test]
+fn test_relations(){
+    use table::fixture::{table, column};
+    let users = table("users", vec![column("user_id", true, None)]);
+    let product = table("product", vec![column("product_id", true, None),
+        column("owner_id", false, Some(("users", "user_id"))),
+        ]);
+    let tables = vec![users, product.clone()];
+    let ref_tables = product.get_all_referenced_table(&tables);
+    assert!(EntityManager::find_relation(&product, "owner", &ref_tables).is_ok());
+    assert_eq!(EntityManager::find_relation(&product, "category", &ref_tables).err(),
+        Some("bazaar.product has no relation named category".to_string()));
+
+    let mut iphone = Dao::new();
+    iphone.set("product_id", &1);
+    iphone.set("owner_id", &10);
+    let mut unowned = Dao::new();
+    unowned.set("product_id", &2);
+    unowned.set_value("owner_id", Type::Null);
+    let daos = vec![iphone, unowned];
+    let mut owner = Dao::new();
+    owner.set("user_id", &10i64);
+    let related = EntityManager::group_related(&daos, "owner_id", vec![owner], "user_id").unwrap();
+    assert_eq!(related.len(), 2);
+    assert_eq!(related[0].len(), 1);
+    assert_eq!(related[0][0].get::<i64>("user_id"), 10);
+    assert!(related[1].is_empty());
+    assert!(EntityManager::group_related(&daos, "owner_id", vec![Dao::new()], "user_id").is_err());
+}
+
+#[test]
+fn test_identity_map(){
+    use platform::postgres::Postgres;
+    use table::fixture::table;
+    let pg = Postgres::new();
+    let em = EntityManager::new(&pg);
+    let product = table("product", vec![]);
+    let category = table("category", vec![]);
+    assert_eq!(EntityManager::identity_key(&product, &Type::I32(1)), EntityManager::identity_key(&product, &Type::I64(1)));
+
+    em.identity_map.borrow_mut().insert(EntityManager::identity_key(&product, &Type::I64(1)), Dao::new());
+    em.identity_map.borrow_mut().insert(EntityManager::identity_key(&category, &Type::I64(1)), Dao::new());
+    em.relations.borrow_mut().insert(("bazaar.category".to_string(), "I64(1)".to_string(), "product".to_string()), vec![]);
+    em.evict_table(&product);
+    assert!(!em.identity_map.borrow().contains_key(&EntityManager::identity_key(&product, &Type::I32(1))));
+    assert!(em.identity_map.borrow().contains_key(&EntityManager::identity_key(&category, &Type::I32(1))));
+    assert!(em.relations.borrow().is_empty());
+}
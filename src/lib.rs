@@ -60,18 +60,32 @@
 //!
 //!
 
+#[macro_use]
 extern crate postgres;
 extern crate rustc_serialize;
 extern crate uuid;
 extern crate chrono;
 extern crate regex;
 extern crate url;
+extern crate openssl;
+#[macro_use]
+extern crate serde;
+#[cfg(test)]
+#[macro_use]
+extern crate serde_derive;
 
 
 pub mod em;
+pub mod session;
 pub mod query;
 pub mod dao;
+pub mod dao_serde;
+pub mod decimal;
+pub mod types;
 pub mod database;
 pub mod platform;
 pub mod table;
+pub mod user_type;
 pub mod writer;
+pub mod router;
+pub mod cursor;
@@ -1,4 +1,10 @@
 pub mod postgres;
+pub mod statement_cache;
+pub mod pg_array;
+pub mod pg_numeric;
+pub mod pg_types;
+pub mod pg_user_type;
+pub mod pg_rust_type;
 
 pub use self::postgres::Postgres;
 
@@ -24,9 +30,3 @@ impl Platform{
     }
     
 }
-
-impl Drop for Platform {
-    fn drop(&mut self) {
-        println!("Warning: Dropping a connection is expensive, please return this to the pool");
-    }
-}
@@ -0,0 +1,143 @@
+//! Binding and reading of one dimensional postgresql arrays as Type::Array,
+//! using the binary array format:
+//! number of dimensions, has nulls flag, element oid,
+//! then the length and lower bound of the dimension,
+//! then each element prefixed by its length, -1 for NULL
+use dao::{Type, JsonValue};
+use platform::postgres::Postgres;
+use platform::pg_user_type::PgUserValue;
+
+use postgres::Result as PgResult;
+use postgres::error::Error as PgError;
+use postgres::types::{Type as PgType, Kind, ToSql, FromSql, IsNull, SessionInfo};
+use std::error::Error;
+use std::io::prelude::*;
+
+/// the array read from a row, only arrays of the element types converted in `read_element` are supported
+pub struct PgArray(pub Vec<Type>);
+
+fn conversion_error(message: String)->PgError{
+    let err: Box<Error+Sync+Send> = message.into();
+    PgError::Conversion(err)
+}
+
+pub fn write_i32(w: &mut Vec<u8>, v: i32){
+    w.extend(&[(v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, v as u8]);
+}
+
+pub fn read_i32<R: Read>(raw: &mut R)->PgResult<i32>{
+    let mut buf = [0u8; 4];
+    try!(raw.read_exact(&mut buf));
+    Ok((buf[0] as i32) << 24 | (buf[1] as i32) << 16 | (buf[2] as i32) << 8 | buf[3] as i32)
+}
+
+/// the element type of the array type
+fn member_type(ty: &PgType)->Option<&PgType>{
+    match *ty.kind(){
+        Kind::Array(ref member) => Some(member),
+        _ => None,
+    }
+}
+
+/// write the values as an array of the array type
+pub fn write_array(values: &Vec<Type>, ty: &PgType, ctx: &SessionInfo)->PgResult<Vec<u8>>{
+    let member = match member_type(ty){
+        Some(member) => member,
+        None => return Err(PgError::WrongType(ty.clone())),
+    };
+    let mut w = vec![];
+    write_i32(&mut w, 1); // number of dimensions
+    write_i32(&mut w, if values.iter().any(|v| *v == Type::Null){ 1 }else{ 0 });
+    write_i32(&mut w, member.oid() as i32);
+    write_i32(&mut w, values.len() as i32);
+    write_i32(&mut w, 1); // lower bound
+    for value in values{
+        if *value == Type::Null{
+            write_i32(&mut w, -1);
+            continue;
+        }
+        let mut element = vec![];
+        match try!(Postgres::to_sql_param(value).to_sql_checked(member, &mut element, ctx)){
+            IsNull::Yes => write_i32(&mut w, -1),
+            IsNull::No => {
+                write_i32(&mut w, element.len() as i32);
+                w.extend(element);
+            }
+        }
+    }
+    Ok(w)
+}
+
+/// read a non null element of the array, also used for the fields of composite values
+pub fn read_element(member: &PgType, raw: &mut &[u8], ctx: &SessionInfo)->PgResult<Type>{
+    let value = match *member{
+        PgType::Bool => Type::Bool(try!(FromSql::from_sql(member, raw, ctx))),
+        PgType::Int2 => Type::I16(try!(FromSql::from_sql(member, raw, ctx))),
+        PgType::Int4 => Type::I32(try!(FromSql::from_sql(member, raw, ctx))),
+        PgType::Int8 => Type::I64(try!(FromSql::from_sql(member, raw, ctx))),
+        PgType::Float4 => Type::F32(try!(FromSql::from_sql(member, raw, ctx))),
+        PgType::Float8 => Type::F64(try!(FromSql::from_sql(member, raw, ctx))),
+        PgType::Numeric => Type::Decimal(try!(FromSql::from_sql(member, raw, ctx))),
+        PgType::Text | PgType::Varchar | PgType::Bpchar | PgType::Name => {
+            Type::String(try!(FromSql::from_sql(member, raw, ctx)))
+        },
+        PgType::Uuid => Type::Uuid(try!(FromSql::from_sql(member, raw, ctx))),
+        PgType::TimestampTZ => Type::DateTime(try!(FromSql::from_sql(member, raw, ctx))),
+        PgType::Timestamp => Type::NaiveDateTime(try!(FromSql::from_sql(member, raw, ctx))),
+        PgType::Date => Type::NaiveDate(try!(FromSql::from_sql(member, raw, ctx))),
+        PgType::Time => Type::NaiveTime(try!(FromSql::from_sql(member, raw, ctx))),
+        PgType::Json | PgType::Jsonb => Type::Json(JsonValue(try!(FromSql::from_sql(member, raw, ctx)))),
+        PgType::Interval => Type::Interval(try!(FromSql::from_sql(member, raw, ctx))),
+        PgType::Inet | PgType::Cidr => Type::Inet(try!(FromSql::from_sql(member, raw, ctx))),
+        PgType::Macaddr => Type::MacAddr(try!(FromSql::from_sql(member, raw, ctx))),
+        PgType::Money => Type::Money(try!(FromSql::from_sql(member, raw, ctx))),
+        PgType::Point => Type::Point(try!(FromSql::from_sql(member, raw, ctx))),
+        _ if <PgUserValue as FromSql>::accepts(member) => try!(PgUserValue::from_sql(member, raw, ctx)).0,
+        _ => return Err(conversion_error(format!("Arrays of {:?} are not supported", member))),
+    };
+    Ok(value)
+}
+
+impl FromSql for PgArray{
+
+    fn from_sql<R: Read>(ty: &PgType, raw: &mut R, ctx: &SessionInfo)->PgResult<Self>{
+        let member = member_type(ty).expect("expecting an array type");
+        let dimensions = try!(read_i32(raw));
+        let _has_nulls = try!(read_i32(raw));
+        let _oid = try!(read_i32(raw));
+        if dimensions == 0{
+            return Ok(PgArray(vec![]));
+        }
+        if dimensions > 1{
+            return Err(conversion_error(format!("Only one dimensional arrays are supported, got {} dimensions", dimensions)));
+        }
+        let len = try!(read_i32(raw));
+        let _lower_bound = try!(read_i32(raw));
+        let mut values = Vec::with_capacity(len as usize);
+        for _ in 0..len{
+            let element_len = try!(read_i32(raw));
+            if element_len < 0{
+                values.push(Type::Null);
+                continue;
+            }
+            let mut element = vec![0u8; element_len as usize];
+            try!(raw.read_exact(&mut element));
+            values.push(try!(read_element(member, &mut &element[..], ctx)));
+        }
+        Ok(PgArray(values))
+    }
+
+    fn accepts(ty: &PgType)->bool{
+        member_type(ty).is_some()
+    }
+}
+
+#[test]
+fn test_read_i32(){
+    let mut w = vec![];
+    write_i32(&mut w, -1);
+    write_i32(&mut w, 70000);
+    let mut raw = &w[..];
+    assert_eq!(read_i32(&mut raw).unwrap(), -1);
+    assert_eq!(read_i32(&mut raw).unwrap(), 70000);
+}
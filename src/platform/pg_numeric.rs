@@ -0,0 +1,138 @@
+//! Lossless binary encoding of postgresql numeric as Decimal:
+//! the number of base 10000 digits, the weight of the first digit,
+//! the sign, the display scale, then the base 10000 digits
+use decimal::Decimal;
+
+use postgres::Result as PgResult;
+use postgres::error::Error as PgError;
+use postgres::types::{Type as PgType, ToSql, FromSql, IsNull, SessionInfo};
+use std::error::Error;
+use std::io::prelude::*;
+use std::str::FromStr;
+
+const NUMERIC_POS: u16 = 0x0000;
+const NUMERIC_NEG: u16 = 0x4000;
+const NUMERIC_NAN: u16 = 0xC000;
+
+fn read_u16<R: Read>(raw: &mut R)->PgResult<u16>{
+    let mut buf = [0u8; 2];
+    try!(raw.read_exact(&mut buf));
+    Ok((buf[0] as u16) << 8 | buf[1] as u16)
+}
+
+fn write_u16(w: &mut Vec<u8>, v: u16){
+    w.push((v >> 8) as u8);
+    w.push(v as u8);
+}
+
+/// the base 10000 digits, the weight of the first one, and the display scale
+fn to_numeric(decimal: &Decimal)->(Vec<u16>, i16, u16){
+    let mut integer = decimal.integer_digits();
+    let mut fraction = decimal.fraction_digits();
+    while integer.len() % 4 != 0{
+        integer.insert(0, '0');
+    }
+    while fraction.len() % 4 != 0{
+        fraction.push('0');
+    }
+    let all = format!("{}{}", integer, fraction);
+    let mut groups:Vec<u16> = (0..all.len() / 4)
+        .map(|i| u16::from_str(&all[i * 4..i * 4 + 4]).unwrap())
+        .collect();
+    let mut weight = (integer.len() / 4) as i16 - 1;
+    while !groups.is_empty() && groups[0] == 0{
+        groups.remove(0);
+        weight -= 1;
+    }
+    while !groups.is_empty() && groups[groups.len() - 1] == 0{
+        groups.pop();
+    }
+    if groups.is_empty(){
+        weight = 0;
+    }
+    (groups, weight, decimal.scale())
+}
+
+/// the text of the numeric, the digit at index i is multiplied by 10000^(weight - i)
+fn from_numeric(groups: &[u16], weight: i16, negative: bool, scale: u16)->String{
+    let group = |i: i32| if i >= 0 && (i as usize) < groups.len(){ groups[i as usize] }else{ 0 };
+    let mut text = String::new();
+    if negative{
+        text.push('-');
+    }
+    if weight < 0{
+        text.push('0');
+    }else{
+        text.push_str(&group(0).to_string());
+        for i in 1..(weight as i32 + 1){
+            text.push_str(&format!("{:04}", group(i)));
+        }
+    }
+    if scale > 0{
+        let mut fraction = String::new();
+        let mut i = weight as i32 + 1;
+        while fraction.len() < scale as usize{
+            fraction.push_str(&format!("{:04}", group(i)));
+            i += 1;
+        }
+        fraction.truncate(scale as usize);
+        text.push('.');
+        text.push_str(&fraction);
+    }
+    text
+}
+
+impl ToSql for Decimal{
+
+    fn to_sql<W: Write+?Sized>(&self, _ty: &PgType, out: &mut W, _ctx: &SessionInfo)->PgResult<IsNull>{
+        let (groups, weight, scale) = to_numeric(self);
+        let mut w = vec![];
+        write_u16(&mut w, groups.len() as u16);
+        write_u16(&mut w, weight as u16);
+        write_u16(&mut w, if self.is_negative(){ NUMERIC_NEG }else{ NUMERIC_POS });
+        write_u16(&mut w, scale);
+        for g in groups{
+            write_u16(&mut w, g);
+        }
+        try!(out.write_all(&w));
+        Ok(IsNull::No)
+    }
+
+    accepts!(PgType::Numeric);
+    to_sql_checked!();
+}
+
+impl FromSql for Decimal{
+
+    fn from_sql<R: Read>(_ty: &PgType, raw: &mut R, _ctx: &SessionInfo)->PgResult<Self>{
+        let ndigits = try!(read_u16(raw));
+        let weight = try!(read_u16(raw)) as i16;
+        let sign = try!(read_u16(raw));
+        let scale = try!(read_u16(raw));
+        if sign == NUMERIC_NAN{
+            let err: Box<Error+Sync+Send> = "NaN numeric can not be represented as Decimal".into();
+            return Err(PgError::Conversion(err));
+        }
+        let mut groups = Vec::with_capacity(ndigits as usize);
+        for _ in 0..ndigits{
+            groups.push(try!(read_u16(raw)));
+        }
+        let text = from_numeric(&groups, weight, sign == NUMERIC_NEG, scale);
+        Ok(Decimal::from_str(&text).unwrap())
+    }
+
+    accepts!(PgType::Numeric);
+}
+
+#[test]
+fn test_numeric_roundtrip(){
+    for text in &["0", "0.00", "12.50", "-12345678.0001", "0.000012", "10000", "123456789012345678901234567890.123456789"]{
+        let decimal = Decimal::from_str(text).unwrap();
+        let (groups, weight, scale) = to_numeric(&decimal);
+        assert_eq!(from_numeric(&groups, weight, decimal.is_negative(), scale), text.to_string());
+    }
+    let (groups, weight, scale) = to_numeric(&Decimal::from_str("12345.678").unwrap());
+    assert_eq!(groups, vec![1, 2345, 6780]);
+    assert_eq!(weight, 1);
+    assert_eq!(scale, 3);
+}
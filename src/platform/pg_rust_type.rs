@@ -0,0 +1,61 @@
+//! The postgresql data types of the rust types,
+//! this module is also used by rustorm_derive so the derive and `Postgres` use the same mapping,
+//! it should not depend on anything else in rustorm
+
+/// the rust types, written without spaces, and their postgresql data type
+pub const RUST_TYPES: &'static [(&'static str, &'static str)] = &[
+    ("bool", "boolean"),
+    ("i8", "char"),
+    ("i16", "smallint"),
+    ("i32", "integer"),
+    ("u32", "oid"),
+    ("i64", "bigint"),
+    ("f32", "real"),
+    ("f64", "double precision"),
+    ("Decimal", "numeric"),
+    ("String", "character varying"),
+    ("Vec<u8>", "bytea"),
+    ("Json", "json"),
+    ("JsonValue", "jsonb"),
+    ("Uuid", "uuid"),
+    ("NaiveDateTime", "timestamp"),
+    ("DateTime<UTC>", "timestamp with time zone"),
+    ("NaiveDate", "date"),
+    ("NaiveTime", "time"),
+    ("HashMap<String,Option<String>>", "hstore"),
+    ("Interval", "interval"),
+    ("Inet", "inet"),
+    ("MacAddr", "macaddr"),
+    ("Money", "money"),
+    ("Point", "point"),
+    ("Range<i32>", "int4range"),
+    ("Range<i64>", "int8range"),
+    ("Range<Decimal>", "numrange"),
+    ("Range<NaiveDateTime>", "tsrange"),
+    ("Range<DateTime<UTC>>", "tstzrange"),
+    ("Range<NaiveDate>", "daterange"),
+];
+
+/// the postgresql data type of the rust type, the spaces in the rust type are ignored,
+/// a `Vec` of a type other than u8 is an array of the element type ie: `Vec<i32>` is `integer[]`
+pub fn db_type(rust_type: &str)->Option<String>{
+    let rust_type = rust_type.replace(" ", "");
+    for &(rust, db) in RUST_TYPES{
+        if rust == rust_type{
+            return Some(db.to_string());
+        }
+    }
+    if rust_type.starts_with("Vec<") && rust_type.ends_with(">"){
+        let element = &rust_type[4..rust_type.len() - 1];
+        return db_type(element).map(|t| format!("{}[]", t));
+    }
+    None
+}
+
+#[test]
+fn test_db_type(){
+    assert_eq!(db_type("HashMap<String, Option<String>>"), Some("hstore".to_string()));
+    assert_eq!(db_type("Vec<u8>"), Some("bytea".to_string()));
+    assert_eq!(db_type("Vec<Range<DateTime<UTC>>>"), Some("tstzrange[]".to_string()));
+    assert_eq!(db_type("u64"), None);
+}
@@ -0,0 +1,294 @@
+//! Binary encoding of interval, inet, cidr, macaddr, money, point and the range types
+use dao::Type;
+use types::{Interval, Inet, MacAddr, Money, Point, Range, RangeBound};
+use platform::postgres::Postgres;
+use platform::pg_array::{read_i32, write_i32, read_element};
+
+use postgres::Result as PgResult;
+use postgres::error::Error as PgError;
+use postgres::types::{Type as PgType, Kind, ToSql, FromSql, IsNull, SessionInfo};
+use std::error::Error;
+use std::io::prelude::*;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// the address families of inet and cidr, AF_INET and AF_INET + 1 in postgresql
+const PGSQL_AF_INET: u8 = 2;
+const PGSQL_AF_INET6: u8 = 3;
+
+/// the flags of a range
+const RANGE_EMPTY: u8 = 0x01;
+const RANGE_LB_INC: u8 = 0x02;
+const RANGE_UB_INC: u8 = 0x04;
+const RANGE_LB_INF: u8 = 0x08;
+const RANGE_UB_INF: u8 = 0x10;
+
+/// the range read from a row, the bounds are converted with `read_element`
+pub struct PgRange(pub Range<Type>);
+
+fn conversion_error(message: String)->PgError{
+    let err: Box<Error+Sync+Send> = message.into();
+    PgError::Conversion(err)
+}
+
+/// months, days and microseconds are sent in reverse order
+impl ToSql for Interval{
+
+    fn to_sql<W: Write+?Sized>(&self, _ty: &PgType, out: &mut W, ctx: &SessionInfo)->PgResult<IsNull>{
+        try!(self.microseconds.to_sql(&PgType::Int8, out, ctx));
+        try!(self.days.to_sql(&PgType::Int4, out, ctx));
+        try!(self.months.to_sql(&PgType::Int4, out, ctx));
+        Ok(IsNull::No)
+    }
+
+    accepts!(PgType::Interval);
+    to_sql_checked!();
+}
+
+impl FromSql for Interval{
+
+    fn from_sql<R: Read>(_ty: &PgType, raw: &mut R, ctx: &SessionInfo)->PgResult<Self>{
+        let microseconds = try!(FromSql::from_sql(&PgType::Int8, raw, ctx));
+        let days = try!(FromSql::from_sql(&PgType::Int4, raw, ctx));
+        let months = try!(FromSql::from_sql(&PgType::Int4, raw, ctx));
+        Ok(Interval::new(months, days, microseconds))
+    }
+
+    accepts!(PgType::Interval);
+}
+
+/// the family, the netmask, whether it is a cidr, the number of address bytes, then the address
+fn inet_bytes(inet: &Inet, is_cidr: bool)->Vec<u8>{
+    let (family, addr) = match inet.addr{
+        IpAddr::V4(ref v4) => (PGSQL_AF_INET, v4.octets().to_vec()),
+        IpAddr::V6(ref v6) => (PGSQL_AF_INET6, v6.octets().to_vec()),
+    };
+    let mut w = vec![family, inet.netmask, if is_cidr{ 1 }else{ 0 }, addr.len() as u8];
+    w.extend(addr);
+    w
+}
+
+impl ToSql for Inet{
+
+    fn to_sql<W: Write+?Sized>(&self, ty: &PgType, out: &mut W, _ctx: &SessionInfo)->PgResult<IsNull>{
+        try!(out.write_all(&inet_bytes(self, *ty == PgType::Cidr)));
+        Ok(IsNull::No)
+    }
+
+    accepts!(PgType::Inet, PgType::Cidr);
+    to_sql_checked!();
+}
+
+impl FromSql for Inet{
+
+    fn from_sql<R: Read>(_ty: &PgType, raw: &mut R, _ctx: &SessionInfo)->PgResult<Self>{
+        let mut header = [0u8; 4];
+        try!(raw.read_exact(&mut header));
+        let (family, netmask, len) = (header[0], header[1], header[3]);
+        let addr = match (family, len){
+            (PGSQL_AF_INET, 4) => {
+                let mut b = [0u8; 4];
+                try!(raw.read_exact(&mut b));
+                IpAddr::V4(Ipv4Addr::new(b[0], b[1], b[2], b[3]))
+            },
+            (PGSQL_AF_INET6, 16) => {
+                let mut b = [0u8; 16];
+                try!(raw.read_exact(&mut b));
+                let s = |i: usize| (b[i * 2] as u16) << 8 | b[i * 2 + 1] as u16;
+                IpAddr::V6(Ipv6Addr::new(s(0), s(1), s(2), s(3), s(4), s(5), s(6), s(7)))
+            },
+            _ => return Err(conversion_error(format!("Invalid address family {} with {} bytes", family, len))),
+        };
+        Ok(Inet{addr: addr, netmask: netmask})
+    }
+
+    accepts!(PgType::Inet, PgType::Cidr);
+}
+
+impl ToSql for MacAddr{
+
+    fn to_sql<W: Write+?Sized>(&self, _ty: &PgType, out: &mut W, _ctx: &SessionInfo)->PgResult<IsNull>{
+        try!(out.write_all(&self.0));
+        Ok(IsNull::No)
+    }
+
+    accepts!(PgType::Macaddr);
+    to_sql_checked!();
+}
+
+impl FromSql for MacAddr{
+
+    fn from_sql<R: Read>(_ty: &PgType, raw: &mut R, _ctx: &SessionInfo)->PgResult<Self>{
+        let mut bytes = [0u8; 6];
+        try!(raw.read_exact(&mut bytes));
+        Ok(MacAddr(bytes))
+    }
+
+    accepts!(PgType::Macaddr);
+}
+
+/// the amount in the smallest unit of the currency as int8
+impl ToSql for Money{
+
+    fn to_sql<W: Write+?Sized>(&self, _ty: &PgType, out: &mut W, ctx: &SessionInfo)->PgResult<IsNull>{
+        self.0.to_sql(&PgType::Int8, out, ctx)
+    }
+
+    accepts!(PgType::Money);
+    to_sql_checked!();
+}
+
+impl FromSql for Money{
+
+    fn from_sql<R: Read>(_ty: &PgType, raw: &mut R, ctx: &SessionInfo)->PgResult<Self>{
+        Ok(Money(try!(FromSql::from_sql(&PgType::Int8, raw, ctx))))
+    }
+
+    accepts!(PgType::Money);
+}
+
+impl ToSql for Point{
+
+    fn to_sql<W: Write+?Sized>(&self, _ty: &PgType, out: &mut W, ctx: &SessionInfo)->PgResult<IsNull>{
+        try!(self.x.to_sql(&PgType::Float8, out, ctx));
+        try!(self.y.to_sql(&PgType::Float8, out, ctx));
+        Ok(IsNull::No)
+    }
+
+    accepts!(PgType::Point);
+    to_sql_checked!();
+}
+
+impl FromSql for Point{
+
+    fn from_sql<R: Read>(_ty: &PgType, raw: &mut R, ctx: &SessionInfo)->PgResult<Self>{
+        let x = try!(FromSql::from_sql(&PgType::Float8, raw, ctx));
+        let y = try!(FromSql::from_sql(&PgType::Float8, raw, ctx));
+        Ok(Point{x: x, y: y})
+    }
+
+    accepts!(PgType::Point);
+}
+
+/// the element type of the range type
+fn subtype(ty: &PgType)->Option<&PgType>{
+    match *ty.kind(){
+        Kind::Range(ref subtype) => Some(subtype),
+        _ => None,
+    }
+}
+
+fn range_flags<T>(range: &Range<T>)->u8{
+    match *range{
+        Range::Empty => RANGE_EMPTY,
+        Range::Between(ref lower, ref upper) => {
+            let mut flags = 0;
+            match *lower{
+                RangeBound::Inclusive(_) => flags |= RANGE_LB_INC,
+                RangeBound::Exclusive(_) => (),
+                RangeBound::Unbounded => flags |= RANGE_LB_INF,
+            }
+            match *upper{
+                RangeBound::Inclusive(_) => flags |= RANGE_UB_INC,
+                RangeBound::Exclusive(_) => (),
+                RangeBound::Unbounded => flags |= RANGE_UB_INF,
+            }
+            flags
+        },
+    }
+}
+
+/// write the bound value prefixed by its length, nothing when unbounded
+fn write_bound(w: &mut Vec<u8>, bound: &RangeBound<Type>, subtype: &PgType, ctx: &SessionInfo)->PgResult<()>{
+    match *bound{
+        RangeBound::Inclusive(ref value) | RangeBound::Exclusive(ref value) => {
+            let mut buf = vec![];
+            match try!(Postgres::to_sql_param(value).to_sql_checked(subtype, &mut buf, ctx)){
+                IsNull::Yes => return Err(conversion_error(format!("Range bounds can not be NULL"))),
+                IsNull::No => {
+                    write_i32(w, buf.len() as i32);
+                    w.extend(buf);
+                }
+            }
+        },
+        RangeBound::Unbounded => (),
+    }
+    Ok(())
+}
+
+/// write the unsigned value as the signed integer type of the column,
+/// postgresql has no unsigned types
+pub fn write_unsigned(x: u64, ty: &PgType, ctx: &SessionInfo)->PgResult<Vec<u8>>{
+    let mut w = vec![];
+    let overflow = || conversion_error(format!("{} does not fit in {}", x, ty.name()));
+    match *ty{
+        PgType::Int2 if x <= i16::max_value() as u64 => try!((x as i16).to_sql(ty, &mut w, ctx)),
+        PgType::Int4 if x <= i32::max_value() as u64 => try!((x as i32).to_sql(ty, &mut w, ctx)),
+        PgType::Int8 if x <= i64::max_value() as u64 => try!((x as i64).to_sql(ty, &mut w, ctx)),
+        PgType::Int2 | PgType::Int4 | PgType::Int8 => return Err(overflow()),
+        _ => return Err(PgError::WrongType(ty.clone())),
+    };
+    Ok(w)
+}
+
+/// write the range as the range type: the flags, then the length and value of each bound
+pub fn write_range(range: &Range<Type>, ty: &PgType, ctx: &SessionInfo)->PgResult<Vec<u8>>{
+    let subtype = match subtype(ty){
+        Some(subtype) => subtype,
+        None => return Err(PgError::WrongType(ty.clone())),
+    };
+    let mut w = vec![range_flags(range)];
+    if let Range::Between(ref lower, ref upper) = *range{
+        try!(write_bound(&mut w, lower, subtype, ctx));
+        try!(write_bound(&mut w, upper, subtype, ctx));
+    }
+    Ok(w)
+}
+
+fn read_bound<R: Read>(raw: &mut R, inclusive: bool, unbounded: bool, subtype: &PgType, ctx: &SessionInfo)
+    ->PgResult<RangeBound<Type>>{
+    if unbounded{
+        return Ok(RangeBound::Unbounded);
+    }
+    let len = try!(read_i32(raw));
+    let mut buf = vec![0u8; len as usize];
+    try!(raw.read_exact(&mut buf));
+    let value = try!(read_element(subtype, &mut &buf[..], ctx));
+    if inclusive{
+        Ok(RangeBound::Inclusive(value))
+    }else{
+        Ok(RangeBound::Exclusive(value))
+    }
+}
+
+impl FromSql for PgRange{
+
+    fn from_sql<R: Read>(ty: &PgType, raw: &mut R, ctx: &SessionInfo)->PgResult<Self>{
+        let subtype = subtype(ty).expect("expecting a range type");
+        let mut flags = [0u8; 1];
+        try!(raw.read_exact(&mut flags));
+        let flags = flags[0];
+        if flags & RANGE_EMPTY != 0{
+            return Ok(PgRange(Range::Empty));
+        }
+        let lower = try!(read_bound(raw, flags & RANGE_LB_INC != 0, flags & RANGE_LB_INF != 0, subtype, ctx));
+        let upper = try!(read_bound(raw, flags & RANGE_UB_INC != 0, flags & RANGE_UB_INF != 0, subtype, ctx));
+        Ok(PgRange(Range::Between(lower, upper)))
+    }
+
+    fn accepts(ty: &PgType)->bool{
+        subtype(ty).is_some()
+    }
+}
+
+#[test]
+fn test_encoding(){
+    use std::str::FromStr;
+    let range = Range::Between(RangeBound::Unbounded, RangeBound::Inclusive(10));
+    assert_eq!(range_flags(&range), RANGE_LB_INF | RANGE_UB_INC);
+    assert_eq!(range_flags(&Range::new(1, 10)), RANGE_LB_INC);
+    assert_eq!(range_flags::<i32>(&Range::Empty), RANGE_EMPTY);
+    let inet = Inet::from_str("192.168.1.0/24").unwrap();
+    assert_eq!(inet_bytes(&inet, true), vec![PGSQL_AF_INET, 24, 1, 4, 192, 168, 1, 0]);
+    let inet = Inet::from_str("::1").unwrap();
+    assert_eq!(inet_bytes(&inet, false).len(), 4 + 16);
+}
@@ -0,0 +1,116 @@
+//! Binary encoding of the values of user defined types:
+//! enum values are the text of the label,
+//! composite values are the number of fields, then the oid, length and value of each field, -1 length for NULL.
+//! Domains are sent by the server as their base type, so they need no special treatment
+use dao::Type;
+use platform::pg_array::{read_i32, write_i32, read_element};
+
+use postgres::Result as PgResult;
+use postgres::error::Error as PgError;
+use postgres::types::{Type as PgType, Kind, Field, ToSql, FromSql, IsNull, SessionInfo};
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::prelude::*;
+
+/// the value of a user defined type read from a row
+pub struct PgUserValue(pub Type);
+
+fn conversion_error(message: String)->PgError{
+    let err: Box<Error+Sync+Send> = message.into();
+    PgError::Conversion(err)
+}
+
+/// write the label of the enum value
+pub fn write_enum(label: &str)->Vec<u8>{
+    label.as_bytes().to_vec()
+}
+
+/// write the fields of the composite value, the oid of each field is the type of the attribute
+/// of the composite type, since the server checks them even when the field is NULL
+pub fn write_composite(fields: &Vec<Type>, ty: &PgType, ctx: &SessionInfo)->PgResult<Vec<u8>>{
+    let attributes = match *ty.kind(){
+        Kind::Composite(ref attributes) => attributes,
+        _ => return Err(PgError::WrongType(ty.clone())),
+    };
+    if attributes.len() != fields.len(){
+        return Err(conversion_error(format!("{} has {} fields, got {} values", ty.name(), attributes.len(), fields.len())));
+    }
+    let mut w = vec![];
+    write_i32(&mut w, fields.len() as i32);
+    for (field, attribute) in fields.iter().zip(attributes){
+        write_i32(&mut w, attribute.type_().oid() as i32);
+        let mut value = vec![];
+        match try!(field.to_sql_checked(attribute.type_(), &mut value, ctx)){
+            IsNull::Yes => write_i32(&mut w, -1),
+            IsNull::No => {
+                write_i32(&mut w, value.len() as i32);
+                w.extend(value);
+            }
+        }
+    }
+    Ok(w)
+}
+
+/// read the fields of the composite value, using the types of the attributes of the composite type
+fn read_composite(raw: &mut &[u8], attributes: &Vec<Field>, ctx: &SessionInfo)->PgResult<Vec<Type>>{
+    let count = try!(read_i32(raw));
+    let mut fields = vec![];
+    for i in 0..count as usize{
+        let _oid = try!(read_i32(raw));
+        let len = try!(read_i32(raw));
+        if len < 0{
+            fields.push(Type::Null);
+            continue;
+        }
+        let mut value = vec![0u8; len as usize];
+        try!(raw.read_exact(&mut value));
+        let field = match attributes.get(i){
+            Some(attribute) => try!(read_element(attribute.type_(), &mut &value[..], ctx)),
+            None => return Err(conversion_error(format!("The composite value has more than {} fields", attributes.len()))),
+        };
+        fields.push(field);
+    }
+    Ok(fields)
+}
+
+/// read the enum label or the composite value, also used for the elements of arrays and composite values
+pub fn read_user_value(ty: &PgType, raw: &mut &[u8], ctx: &SessionInfo)->PgResult<Type>{
+    match *ty.kind(){
+        Kind::Enum(_) => match String::from_utf8(raw.to_vec()){
+            Ok(label) => Ok(Type::Enum(label)),
+            Err(e) => Err(PgError::Conversion(Box::new(e))),
+        },
+        Kind::Composite(ref attributes) => Ok(Type::Composite(try!(read_composite(raw, attributes, ctx)))),
+        _ => Err(PgError::WrongType(ty.clone())),
+    }
+}
+
+impl FromSql for PgUserValue{
+
+    fn from_sql<R: Read>(ty: &PgType, raw: &mut R, ctx: &SessionInfo)->PgResult<Self>{
+        match *ty{
+            PgType::Other(ref other) if other.name() == "citext" => {
+                return Ok(PgUserValue(Type::String(try!(FromSql::from_sql(ty, raw, ctx)))));
+            },
+            PgType::Other(ref other) if other.name() == "hstore" => {
+                let map:HashMap<String, Option<String>> = try!(FromSql::from_sql(ty, raw, ctx));
+                return Ok(PgUserValue(Type::Map(map)));
+            },
+            _ => (),
+        }
+        let mut buf = vec![];
+        try!(raw.read_to_end(&mut buf));
+        Ok(PgUserValue(try!(read_user_value(ty, &mut &buf[..], ctx))))
+    }
+
+    /// the enum and composite types, and the extension types read as text or map
+    fn accepts(ty: &PgType)->bool{
+        match *ty.kind(){
+            Kind::Enum(_) | Kind::Composite(_) => true,
+            _ => match *ty{
+                PgType::Other(ref other) => other.name() == "citext" || other.name() == "hstore",
+                _ => false,
+            },
+        }
+    }
+}
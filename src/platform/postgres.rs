@@ -1,27 +1,70 @@
 use query::Query;
-use table::{Table, Column, Foreign};
+use table::{Table, Column, Foreign, Version};
 use dao::Dao;
 
 use postgres::Connection;
-use postgres::SslMode;
+use postgres::SslMode as PgSslMode;
+use postgres::{ConnectParams, ConnectTarget, UserInfo};
+use openssl::ssl::{SslContext, SslMethod, SSL_VERIFY_PEER, SSL_VERIFY_NONE};
+use openssl::x509::X509FileType;
+use std::path::PathBuf;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use std::cell::{Cell, RefCell};
+use platform::statement_cache::{StatementCache, CacheStats, DEFAULT_STATEMENT_CACHE_SIZE};
+use postgres::Statement;
+use postgres::error::Error as PgError;
 use regex::Regex;
-use dao::Type;
+use dao::{Type, JsonValue};
 use query::SqlType;
 use database::{Database, DatabaseDev, DatabaseDDL};
 use postgres::types::Type as PgType;
-use postgres::types::ToSql;
+use postgres::types::{ToSql, FromSql};
+use platform::pg_array::{self, PgArray};
+use platform::pg_user_type::{self, PgUserValue};
+use platform::pg_types::{self, PgRange};
+use platform::pg_rust_type;
+use postgres::types::{IsNull, SessionInfo};
+use postgres::Result as PgResult;
+use std::io::prelude::*;
+use user_type::{UserType, UserTypeKind};
 use dao::DaoResult;
 use writer::SqlFrag;
 use postgres::rows::Row;
 use database::SqlOption;
 use std::error::Error;
 use database::DbConfig;
+use database::SslMode;
 
 pub struct Postgres {
     config: Option<DbConfig>,
     pub conn: Option<Connection>,
+    /// whether a transaction was started with begin, and not yet committed or rolled back
+    transacted: Cell<bool>,
+    /// the prepared statements of this connection
+    statements: RefCell<StatementCache>,
+    /// the user defined types, loaded on first use when generating code
+    user_types: RefCell<Option<Vec<UserType>>>,
 }
 
+/// postgres 0.11 has no connect timeout and its socket is not exposed,
+/// so the host is probed with a separate tcp connect within the timeout first,
+/// an unreachable host is then skipped instead of waiting for the os to give up,
+/// the probe connection is closed right away and does not limit the real connection
+fn check_reachable(host:&str, port:u16, timeout:Duration)->Result<(), String>{
+    let addrs = match (host, port).to_socket_addrs(){
+        Ok(addrs) => addrs,
+        Err(e) => return Err(format!("{}: {}", host, e)),
+    };
+    let mut error = format!("{}: no address found", host);
+    for addr in addrs{
+        match TcpStream::connect_timeout(&addr, timeout){
+            Ok(_) => return Ok(()),
+            Err(e) => error = format!("{}: {}", host, e),
+        }
+    }
+    Err(error)
+}
 
 impl Postgres{
     
@@ -29,23 +72,185 @@ impl Postgres{
     /// useful when just building sql queries specific to this platform
     /// inexpensive operation, so can have multiple instances
     pub fn new()->Self{
-        Postgres{conn:None, config: None}
+        Postgres{
+            conn:None, 
+            config: None, 
+            transacted: Cell::new(false), 
+            statements: RefCell::new(StatementCache::new(0)),
+            user_types: RefCell::new(None),
+        }
     }
     
     pub fn connect_with_url(url:&str)->Result<Self, String>{
-        let conn = Connection::connect(url, &SslMode::None);
-        match conn{
-            Ok(conn) => {
-                let config = DbConfig::from_url(url);
-                let pg = Postgres{config: Some(config), conn: Some(conn)};
-                Ok(pg)
-            },
-            Err(e) => {
-                let error = format!("Unable to connect to database due to {}", e.description());
-                println!("{:?}",e);
-                Err(error)
+        let config = try!(DbConfig::parse(url));
+        Self::connect_with_config(&config)
+    }
+    
+    /// connect to the database, when there are multiple hosts,
+    /// each host is tried in order until a connection is made
+    pub fn connect_with_config(config:&DbConfig)->Result<Self, String>{
+        let cache_size = config.statement_cache_size().unwrap_or(DEFAULT_STATEMENT_CACHE_SIZE);
+        let mut errors = vec![];
+        for &(ref host, ref port) in config.hosts(){
+            let target = if host.starts_with("/"){
+                ConnectTarget::Unix(PathBuf::from(host))
+            }else{
+                if let Some(timeout) = config.reachable_timeout(){
+                    if let Err(e) = check_reachable(host, port.unwrap_or(5432), timeout){
+                        errors.push(e);
+                        continue;
+                    }
+                }
+                ConnectTarget::Tcp(host.to_string())
+            };
+            let user = match config.username(){
+                Some(username) => Some(UserInfo{
+                        user: username.to_string(), 
+                        password: config.password().map(|p| p.to_string())
+                    }),
+                None => None,
+            };
+            // ssl is not used on unix sockets
+            let ssl = match target{
+                ConnectTarget::Unix(_) => PgSslMode::None,
+                _ => try!(Self::ssl_mode(config)),
+            };
+            let params = ConnectParams{
+                target: target,
+                port: port.clone(),
+                user: user,
+                database: Some(config.database().to_string()),
+                options: config.runtime_options(),
+            };
+            match Connection::connect(params, &ssl){
+                Ok(conn) => {
+                    let pg = Postgres{
+                        config: Some(config.clone()), 
+                        conn: Some(conn), 
+                        transacted: Cell::new(false),
+                        statements: RefCell::new(StatementCache::new(cache_size)),
+                        user_types: RefCell::new(None),
+                    };
+                    return Ok(pg);
+                },
+                Err(e) => {
+                    errors.push(format!("{}: {}", host, e.description()));
+                }
             }
         }
+        Err(format!("Unable to connect to database due to {}", errors.join(", ")))
+    }
+    
+    /// the hits, misses, evicted and invalidated statements of the prepared statement cache
+    pub fn statement_cache_stats(&self)->CacheStats{
+        self.statements.borrow().stats()
+    }
+
+    /// execute the statements which have no parameters and no return, ie: BEGIN, COMMIT
+    fn batch_execute(&self, sql:&str)->Result<(), String>{
+        match self.conn{
+            Some(ref conn) => conn.batch_execute(sql).map_err(|e| format!("Unable to execute {} due to {}", sql, e)),
+            None => Err(format!("Unable to execute {}, there is no connection", sql)),
+        }
+    }
+
+    /// execute using the cached prepared statement of the sql,
+    /// when the statement is no longer valid due to schema changes, it is prepared again and retried once
+    fn with_statement<F, R>(&self, sql:&str, f:F)->Result<R, PgError> 
+        where F: Fn(&Statement)->Result<R, PgError>{
+        let conn:&Connection = self.conn.as_ref().unwrap();
+        let mut cache = self.statements.borrow_mut();
+        let result = {
+            let stmt = try!(cache.prepare(conn, sql));
+            f(&stmt)
+        };
+        match result{
+            Err(ref e) if cache.invalidate(conn, sql, e) => (),
+            result => return result,
+        };
+        let stmt = try!(cache.prepare(conn, sql));
+        f(&stmt)
+    }
+    
+    /// the names of the columns in the daos, a repeated column name is suffixed with its position,
+    /// or the next position which is not the name of another column, so no value is lost
+    fn result_column_names(names:&[&str])->Vec<String>{
+        let mut result:Vec<String> = vec![];
+        for (index, name) in names.iter().enumerate(){
+            let mut column_name = name.to_string();
+            let mut suffix = index;
+            while result.contains(&column_name)
+                || (column_name != *name && names.contains(&&column_name[..])){
+                column_name = format!("{}_{}", name, suffix);
+                suffix += 1;
+            }
+            result.push(column_name);
+        }
+        result
+    }
+
+    /// convert the rows returned by the statement into daos, in the order of the columns
+    fn read_rows(stmt:&Statement, param:&[&ToSql])->Result<Vec<Dao>, PgError>{
+        let names:Vec<&str> = stmt.columns().iter().map(|c| c.name()).collect();
+        let column_names = Self::result_column_names(&names);
+        let mut daos = vec![];
+        for row in try!(stmt.query(param)){
+            let mut dao = Dao::new();
+            for (index, c) in row.columns().iter().enumerate(){
+                let dtype = c.type_();
+                let rtype = Self::from_sql_to_rust_type(&dtype, &row, index);
+                dao.set_value(&column_names[index], rtype);
+            }
+            daos.push(dao);
+        }
+        Ok(daos)
+    }
+    
+    /// build the ssl negotiator based on the sslmode, sslrootcert, sslcert and sslkey options
+    fn ssl_mode(config:&DbConfig)->Result<PgSslMode, String>{
+        let mode = try!(config.ssl_mode());
+        let verify_peer = match mode{
+            SslMode::Disable => return Ok(PgSslMode::None),
+            SslMode::Prefer => false,
+            SslMode::Require => config.ssl_root_cert().is_some(),
+            SslMode::VerifyCa => true,
+            // the host is verified against the subject alternative names of the certificate,
+            // openssl 0.6 has no api to read them, so only verify-ca is supported
+            SslMode::VerifyFull => return Err(format!("sslmode=verify-full is not supported, openssl 0.6 can not read the subject alternative names of the certificate, use verify-ca")),
+        };
+        let mut context = match SslContext::new(SslMethod::Sslv23){
+            Ok(context) => context,
+            Err(e) => return Err(format!("Unable to create ssl context due to {}", e)),
+        };
+        if verify_peer{
+            context.set_verify(SSL_VERIFY_PEER, None);
+            let loaded = match config.ssl_root_cert(){
+                Some(root_cert) => context.set_CA_file(root_cert)
+                        .map_err(|e| format!("Unable to load sslrootcert {} due to {}", root_cert, e)),
+                None => context.set_default_verify_paths()
+                        .map_err(|e| format!("Unable to load the default root certificates due to {}", e)),
+            };
+            try!(loaded);
+        }else{
+            context.set_verify(SSL_VERIFY_NONE, None);
+        }
+        match (config.ssl_cert(), config.ssl_key()){
+            (Some(cert), Some(key)) => {
+                if let Err(e) = context.set_certificate_file(cert, X509FileType::PEM){
+                    return Err(format!("Unable to load sslcert {} due to {}", cert, e));
+                }
+                if let Err(e) = context.set_private_key_file(key, X509FileType::PEM){
+                    return Err(format!("Unable to load sslkey {} due to {}", key, e));
+                }
+            },
+            (None, None) => (),
+            _ => return Err(format!("sslcert and sslkey must be specified together")),
+        }
+        let negotiator = Box::new(context);
+        match mode{
+            SslMode::Prefer => Ok(PgSslMode::Prefer(negotiator)),
+            _ => Ok(PgSslMode::Require(negotiator)),
+        }
     }
     
 
@@ -57,18 +262,88 @@ impl Postgres{
     fn from_rust_type_tosql<'a>(types: &'a Vec<Type>)->Vec<&'a ToSql>{
         let mut params:Vec<&ToSql> = vec![];
         for t in types{
-            match t {
-                &Type::String(ref x) => {
-                    params.push(x);
-                },
-                &Type::Uuid(ref x) => {
-                    params.push(x);
-                },
-                _ => panic!("not yet here {:?}", t),
-            };
+            params.push(Self::to_sql_param(t));
         }
         params
     }
+
+    /// the postgresql native value of the type,
+    /// NULL, unsigned, arrays, enums, composite and range values are converted by the ToSql of Type itself
+    pub fn to_sql_param(t:&Type)->&ToSql{
+        match t {
+            &Type::String(ref x) => {
+                x
+            },
+            &Type::Uuid(ref x) => {
+                x
+            },
+            &Type::Bool(ref x) => {
+                x
+            },
+            &Type::I8(ref x) => {
+                x
+            },
+            &Type::I16(ref x) => {
+                x
+            },
+            &Type::I32(ref x) => {
+                x
+            },
+            &Type::I64(ref x) => {
+                x
+            },
+            &Type::U32(ref x) => {
+                x
+            },
+            &Type::F32(ref x) => {
+                x
+            },
+            &Type::F64(ref x) => {
+                x
+            },
+            &Type::Decimal(ref x) => {
+                x
+            },
+            &Type::VecU8(ref x) => {
+                x
+            },
+            &Type::DateTime(ref x) => {
+                x
+            },
+            &Type::NaiveDate(ref x) => {
+                x
+            },
+            &Type::NaiveTime(ref x) => {
+                x
+            },
+            &Type::NaiveDateTime(ref x) => {
+                x
+            },
+            &Type::Json(ref x) => {
+                &x.0
+            },
+            &Type::Interval(ref x) => {
+                x
+            },
+            &Type::Inet(ref x) => {
+                x
+            },
+            &Type::MacAddr(ref x) => {
+                x
+            },
+            &Type::Money(ref x) => {
+                x
+            },
+            &Type::Point(ref x) => {
+                x
+            },
+            &Type::Null | &Type::U8(_) | &Type::U16(_) | &Type::U64(_)
+                | &Type::Array(_) | &Type::Enum(_) | &Type::Composite(_) | &Type::Range(_) => {
+                t
+            },
+            _ => panic!("not yet here {:?}", t),
+        }
+    }
     
     /// convert a record of a row into rust type
     fn from_sql_to_rust_type(dtype:&PgType, row: &Row, index:usize)->Type{
@@ -97,7 +372,7 @@ impl Postgres{
              &PgType::Numeric => {
                 let value = row.get_opt(index);
                  match value{
-                    Ok(value) => Type::F64(value),
+                    Ok(value) => Type::Decimal(value),
                     Err(_) => Type::Null,
                 }
             },
@@ -108,10 +383,10 @@ impl Postgres{
                     Err(_) => Type::Null,
                 }
             },
-            &PgType::Json => {
+            &PgType::Json | &PgType::Jsonb => {
                 let value = row.get_opt(index);
                  match value{
-                    Ok(value) => Type::String(value),
+                    Ok(value) => Type::Json(JsonValue(value)),
                     Err(_) => Type::Null,
                 }
             },
@@ -129,6 +404,62 @@ impl Postgres{
                     Err(_) => Type::Null,
                 }
             },
+            &PgType::Interval => {
+                let value = row.get_opt(index);
+                 match value{
+                    Ok(value) => Type::Interval(value),
+                    Err(_) => Type::Null,
+                }
+            },
+            &PgType::Inet | &PgType::Cidr => {
+                let value = row.get_opt(index);
+                 match value{
+                    Ok(value) => Type::Inet(value),
+                    Err(_) => Type::Null,
+                }
+            },
+            &PgType::Macaddr => {
+                let value = row.get_opt(index);
+                 match value{
+                    Ok(value) => Type::MacAddr(value),
+                    Err(_) => Type::Null,
+                }
+            },
+            &PgType::Money => {
+                let value = row.get_opt(index);
+                 match value{
+                    Ok(value) => Type::Money(value),
+                    Err(_) => Type::Null,
+                }
+            },
+            &PgType::Point => {
+                let value = row.get_opt(index);
+                 match value{
+                    Ok(value) => Type::Point(value),
+                    Err(_) => Type::Null,
+                }
+            },
+            _ if <PgRange as FromSql>::accepts(dtype) => {
+                let value:Result<PgRange, _> = row.get_opt(index);
+                 match value{
+                    Ok(value) => Type::Range(Box::new(value.0)),
+                    Err(_) => Type::Null,
+                }
+            },
+            _ if <PgUserValue as FromSql>::accepts(dtype) => {
+                let value:Result<PgUserValue, _> = row.get_opt(index);
+                 match value{
+                    Ok(value) => value.0,
+                    Err(_) => Type::Null,
+                }
+            },
+            _ if <PgArray as FromSql>::accepts(dtype) => {
+                let value:Result<PgArray, _> = row.get_opt(index);
+                 match value{
+                    Ok(value) => Type::Array(value.0),
+                    Err(_) => Type::Null,
+                }
+            },
              
             _ => panic!("Type {:?} is not covered!", dtype)
         }
@@ -199,16 +530,24 @@ impl Postgres{
             let not_null:bool = row.get("notnull");
             let db_data_type:String = row.get("data_type");
             //TODO: temporarily regex the data type to extract the size as well
-            let re = match Regex::new("(.+)\\((.+)\\)") {
+            let re = match Regex::new("^(.+)\\((.+)\\)(\\[\\])?$") {
                  Ok(re) => re,
                  Err(err) => panic!("{}", err),
             };
 
+            let mut precision = None;
+            let mut scale = None;
             let db_data_type = if re.is_match(&db_data_type){
                 let cap = re.captures(&db_data_type).unwrap();
                 let data_type = cap.at(1).unwrap().to_string();
                 let size = cap.at(2).unwrap().to_string();//TODO::can be use in the later future
-                data_type
+                if data_type == "numeric"{
+                    let mut split = size.split(",");
+                    precision = split.next().and_then(|p| p.trim().parse().ok());
+                    scale = split.next().and_then(|s| s.trim().parse().ok()).or(Some(0));
+                }
+                let array = cap.at(3).unwrap_or("");
+                format!("{}{}", data_type, array)
             }else{
                 db_data_type
             };
@@ -264,6 +603,8 @@ impl Postgres{
                     not_null:not_null,
                     foreign:foreign,
                     is_inherited:false,//will be corrected later in the get_meta_data
+                    precision:precision,
+                    scale:scale,
                 };
             columns.push(column);
         }
@@ -272,6 +613,21 @@ impl Postgres{
         Self::unify_primary_and_foreign_column(&columns)
     }
 
+    /// the user defined type named by the database data type,
+    /// the user types are loaded from the database on first use
+    fn find_user_type(&self, db_type:&str)->Option<UserType>{
+        if self.user_types.borrow().is_none(){
+            if self.conn.is_none(){
+                return None;
+            }
+            self.get_user_types();
+        }
+        match *self.user_types.borrow(){
+            Some(ref user_types) => user_types.iter().find(|u| u.is_named(db_type)).cloned(),
+            None => None,
+        }
+    }
+
     /// column that is both primary and foreign should be unified
     fn unify_primary_and_foreign_column(columns:&Vec<Column>)->Vec<Column>{
         let mut unified_columns = Vec::new();
@@ -305,6 +661,46 @@ impl Postgres{
 }
 
 
+/// the values without a native ToSql: NULL, unsigned, arrays, enums, composite and range values,
+/// the other values are converted by their native ToSql
+impl ToSql for Type{
+
+    fn to_sql<W: Write+?Sized>(&self, ty: &PgType, out: &mut W, ctx: &SessionInfo)->PgResult<IsNull>{
+        let buf = match *self{
+            Type::Null => return Ok(IsNull::Yes),
+            Type::Array(ref values) => try!(pg_array::write_array(values, ty, ctx)),
+            Type::Enum(ref label) => pg_user_type::write_enum(label),
+            Type::Composite(ref fields) => try!(pg_user_type::write_composite(fields, ty, ctx)),
+            Type::Range(ref range) => try!(pg_types::write_range(range, ty, ctx)),
+            Type::U8(x) => try!(pg_types::write_unsigned(x as u64, ty, ctx)),
+            Type::U16(x) => try!(pg_types::write_unsigned(x as u64, ty, ctx)),
+            Type::U64(x) => try!(pg_types::write_unsigned(x, ty, ctx)),
+            _ => {
+                let mut buf = vec![];
+                match try!(Postgres::to_sql_param(self).to_sql_checked(ty, &mut buf, ctx)){
+                    IsNull::Yes => return Ok(IsNull::Yes),
+                    IsNull::No => buf,
+                }
+            },
+        };
+        try!(out.write_all(&buf));
+        Ok(IsNull::No)
+    }
+
+    /// the type of the value is only known in to_sql_checked
+    fn accepts(_ty: &PgType)->bool{
+        true
+    }
+
+    fn to_sql_checked(&self, ty: &PgType, out: &mut Write, ctx: &SessionInfo)->PgResult<IsNull>{
+        match *self{
+            Type::Null | Type::U8(_) | Type::U16(_) | Type::U64(_)
+                | Type::Array(_) | Type::Enum(_) | Type::Composite(_) | Type::Range(_) => self.to_sql(ty, out, ctx),
+            _ => Postgres::to_sql_param(self).to_sql_checked(ty, out, ctx),
+        }
+    }
+}
+
 impl Database for Postgres{
     
     fn get_config(&self)->DbConfig{
@@ -316,15 +712,47 @@ impl Database for Postgres{
         let version = dao.get("server_version");
         version
     }
-    fn begin(&self){}
-    fn commit(&self){}
-    fn rollback(&self){}
-    fn is_transacted(&self)->bool{false}
-    fn is_closed(&self)->bool{false}
-    fn is_connected(&self)->bool{false}
+    fn begin(&self)->Result<(), String>{
+        try!(self.batch_execute("BEGIN"));
+        self.transacted.set(true);
+        Ok(())
+    }
+    /// the transaction is ended even when the commit fails, since postgresql rolls it back
+    fn commit(&self)->Result<(), String>{
+        self.transacted.set(false);
+        self.batch_execute("COMMIT")
+    }
+    fn rollback(&self)->Result<(), String>{
+        self.transacted.set(false);
+        self.batch_execute("ROLLBACK")
+    }
+    fn is_transacted(&self)->bool{
+        self.transacted.get()
+    }
+    fn is_closed(&self)->bool{
+        !self.is_connected()
+    }
+    /// connected and the connection is not in a broken state
+    fn is_connected(&self)->bool{
+        match self.conn{
+            Some(ref conn) => !conn.is_desynchronized(),
+            None => false,
+        }
+    }
     fn close(&self){}
-    fn is_valid(&self)->bool{false}
-    fn reset(&self){}
+    /// a simple query on the connection succeeds
+    fn is_valid(&self)->bool{
+        if !self.is_connected(){
+            return false;
+        }
+        self.conn.as_ref().unwrap().batch_execute("SELECT 1").is_ok()
+    }
+    /// discard the session state such as temporary tables, cursors and settings,
+    /// this is DISCARD ALL except DEALLOCATE ALL, so the cached prepared statements are kept
+    fn reset(&self)->Result<(), String>{
+        self.batch_execute("CLOSE ALL; SET SESSION AUTHORIZATION DEFAULT; RESET ALL; UNLISTEN *; \
+                SELECT pg_advisory_unlock_all(); DISCARD PLANS; DISCARD SEQUENCES; DISCARD TEMP;")
+    }
     
     /// return this list of options, supported features in the database
     fn sql_options(&self)->Vec<SqlOption>{
@@ -365,30 +793,31 @@ impl Database for Postgres{
         let sql_frag = self.build_insert(query);
         self.execute_sql_with_one_return(&sql_frag.sql, &sql_frag.params)
     }
-    fn update(&self, query:&Query)->Dao{panic!("not yet")}
-    fn delete(&self, query:&Query)->Result<usize, String>{panic!("not yet");}
+    fn update(&self, query:&Query)->Dao{
+        let sql_frag = self.build_update(query);
+        self.execute_sql_with_one_return(&sql_frag.sql, &sql_frag.params)
+    }
+    fn delete(&self, query:&Query)->Result<usize, String>{
+        let sql_frag = self.build_delete(query);
+        self.execute_sql(&sql_frag.sql, &sql_frag.params)
+    }
 
     fn execute_sql_with_return(&self, sql:&str, params:&Vec<Type>)->Vec<Dao>{
+        match self.try_execute_sql_with_return(sql, params){
+            Ok(daos) => daos,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    fn try_execute_sql_with_return(&self, sql:&str, params:&Vec<Type>)->Result<Vec<Dao>, String>{
         println!("SQL: \n{}", sql);
         println!("param: {:?}", params);
         assert!(self.conn.is_some());
-        let stmt = self.conn.as_ref().unwrap().prepare(sql).unwrap();
-        let mut daos = vec![];
         let param = Self::from_rust_type_tosql(params);
-        for row in stmt.query(&param).unwrap() {
-            let columns = row.columns();
-            let mut index = 0;
-            let mut dao = Dao::new();
-            for c in columns{
-                let column_name = c.name();
-                let dtype = c.type_();
-                let rtype = Self::from_sql_to_rust_type(&dtype, &row, index);
-                dao.set_value(column_name, rtype);
-                index += 1;
-            }
-            daos.push(dao);
+        match self.with_statement(sql, |stmt| Self::read_rows(stmt, &param)){
+            Ok(daos) => Ok(daos),
+            Err(e) => Err(format!("Unable to execute the sql due to {}", e)),
         }
-        daos
     }
     
     fn execute_sql_with_one_return(&self, sql:&str, params:&Vec<Type>)->Dao{
@@ -405,7 +834,7 @@ impl Database for Postgres{
         println!("param: {:?}", params);
         let to_sql_types = Self::from_rust_type_tosql(params);
         assert!(self.conn.is_some());
-        let result = self.conn.as_ref().unwrap().execute(sql, &to_sql_types);
+        let result = self.with_statement(sql, |stmt| stmt.execute(&to_sql_types));
         let result = match result{
             Ok(x) => { Ok(x as usize)},
             Err(e) => {
@@ -516,6 +945,7 @@ impl DatabaseDev for Postgres{
             }
         }
 
+        let version = Version::detect(&columns);
         Table{
             schema:schema.to_string(),
             name:table.to_string(),
@@ -523,6 +953,7 @@ impl DatabaseDev for Postgres{
             sub_table:subclass,
             comment:comment,
             columns:columns,
+            version:version,
         }
     }
 
@@ -579,6 +1010,147 @@ impl DatabaseDev for Postgres{
         None
     }
 
+    fn get_user_types(&self)->Vec<UserType>{
+        let mut user_types = vec![];
+        let enum_sql = "
+                SELECT
+                    pg_namespace.nspname AS schema,
+                    pg_type.typname AS name,
+                    obj_description(pg_type.oid) AS comment,
+                    pg_enum.enumlabel AS label
+                FROM pg_type
+                    JOIN pg_namespace
+                        ON pg_namespace.oid = pg_type.typnamespace
+                    JOIN pg_enum
+                        ON pg_enum.enumtypid = pg_type.oid
+                WHERE pg_type.typtype = 'e'
+                    AND pg_namespace.nspname NOT IN ('information_schema', 'pg_catalog', 'pg_toast')
+                ORDER BY nspname, typname, pg_enum.enumsortorder
+                ";
+        assert!(self.conn.is_some());
+        let stmt = self.conn.as_ref().unwrap().prepare(&enum_sql).unwrap();
+        for row in stmt.query(&[]).unwrap() {
+            let schema:String = row.get("schema");
+            let name:String = row.get("name");
+            let label:String = row.get("label");
+            let is_same = match user_types.last(){
+                Some(&UserType{schema: ref s, name: ref n, ..}) => *s == schema && *n == name,
+                None => false,
+            };
+            if !is_same{
+                let comment:Option<String> = match row.get_opt("comment"){
+                        Ok(x) => Some(x),
+                        Err(_) => None
+                    };
+                user_types.push(UserType{schema: schema, name: name, kind: UserTypeKind::Enum(vec![]), comment: comment});
+            }
+            if let Some(&mut UserType{kind: UserTypeKind::Enum(ref mut labels), ..}) = user_types.last_mut(){
+                labels.push(label);
+            }
+        }
+
+        let domain_sql = "
+                SELECT
+                    pg_namespace.nspname AS schema,
+                    pg_type.typname AS name,
+                    obj_description(pg_type.oid) AS comment,
+                    pg_catalog.format_type(pg_type.typbasetype, pg_type.typtypmod) AS base_type
+                FROM pg_type
+                    JOIN pg_namespace
+                        ON pg_namespace.oid = pg_type.typnamespace
+                WHERE pg_type.typtype = 'd'
+                    AND pg_namespace.nspname NOT IN ('information_schema', 'pg_catalog', 'pg_toast')
+                ORDER BY nspname, typname
+                ";
+        let stmt = self.conn.as_ref().unwrap().prepare(&domain_sql).unwrap();
+        for row in stmt.query(&[]).unwrap() {
+            let comment:Option<String> = match row.get_opt("comment"){
+                    Ok(x) => Some(x),
+                    Err(_) => None
+                };
+            user_types.push(UserType{
+                schema: row.get("schema"),
+                name: row.get("name"),
+                kind: UserTypeKind::Domain(row.get("base_type")),
+                comment: comment,
+            });
+        }
+
+        // the composite types which are not the row types of tables
+        let composite_sql = "
+                SELECT
+                    pg_namespace.nspname AS schema,
+                    pg_type.typname AS name,
+                    obj_description(pg_type.oid) AS comment,
+                    pg_attribute.attname AS attribute,
+                    pg_catalog.format_type(pg_attribute.atttypid, pg_attribute.atttypmod) AS data_type
+                FROM pg_type
+                    JOIN pg_namespace
+                        ON pg_namespace.oid = pg_type.typnamespace
+                    JOIN pg_class
+                        ON pg_class.oid = pg_type.typrelid
+                    JOIN pg_attribute
+                        ON pg_attribute.attrelid = pg_class.oid
+                WHERE pg_type.typtype = 'c'
+                    AND pg_class.relkind = 'c'
+                    AND pg_attribute.attnum > 0
+                    AND pg_attribute.attisdropped = false
+                    AND pg_namespace.nspname NOT IN ('information_schema', 'pg_catalog', 'pg_toast')
+                ORDER BY nspname, typname, pg_attribute.attnum
+                ";
+        let mut composites:Vec<UserType> = vec![];
+        let stmt = self.conn.as_ref().unwrap().prepare(&composite_sql).unwrap();
+        for row in stmt.query(&[]).unwrap() {
+            let schema:String = row.get("schema");
+            let name:String = row.get("name");
+            let is_same = match composites.last(){
+                Some(&UserType{schema: ref s, name: ref n, ..}) => *s == schema && *n == name,
+                None => false,
+            };
+            if !is_same{
+                let comment:Option<String> = match row.get_opt("comment"){
+                        Ok(x) => Some(x),
+                        Err(_) => None
+                    };
+                composites.push(UserType{schema: schema, name: name, kind: UserTypeKind::Composite(vec![]), comment: comment});
+            }
+            let db_data_type:String = row.get("data_type");
+            let column = Column{
+                name: row.get("attribute"),
+                data_type: String::new(),//resolved below, since the attributes may be other user defined types
+                db_data_type: db_data_type,
+                comment: None,
+                is_primary: false,
+                is_unique: false,
+                default: None,
+                not_null: false,
+                foreign: None,
+                is_inherited: false,
+                precision: None,
+                scale: None,
+            };
+            if let Some(&mut UserType{kind: UserTypeKind::Composite(ref mut columns), ..}) = composites.last_mut(){
+                columns.push(column);
+            }
+        }
+        user_types.extend(composites);
+        // cached before resolving the attributes, which may refer to the other user defined types
+        *self.user_types.borrow_mut() = Some(user_types.clone());
+        for user_type in &mut user_types{
+            if let UserTypeKind::Composite(ref mut columns) = user_type.kind{
+                for c in columns{
+                    let db_data_type = match c.db_data_type.find('('){
+                        Some(i) => c.db_data_type[..i].to_string(),
+                        None => c.db_data_type.clone(),
+                    };
+                    c.data_type = self.dbtype_to_rust_type(&db_data_type).1;
+                }
+            }
+        }
+        *self.user_types.borrow_mut() = Some(user_types.clone());
+        user_types
+    }
+
     fn get_inherited_columns(&self, schema:&str, table:&str)->Vec<String>{
         let sql = "
                 SELECT nmsp_parent.nspname    AS parent_schema,
@@ -616,6 +1188,10 @@ impl DatabaseDev for Postgres{
     /// get the rust data type names from database data type names
     /// will be used in source code generation
     fn dbtype_to_rust_type(&self, db_type: &str)->(Vec<String>, String){
+        if db_type.ends_with("[]"){
+            let (imports, element) = self.dbtype_to_rust_type(&db_type[..db_type.len() - 2]);
+            return (imports, format!("Vec<{}>", element));
+        }
         let db_type = match db_type{
             "boolean" => {
                 (vec![], "bool".to_string() )
@@ -638,20 +1214,20 @@ impl DatabaseDev for Postgres{
             "real" => {
                 (vec![], "f32".to_string() )
             },
-            "double precision" | "numeric" => {
+            "double precision" => {
                 (vec![], "f64".to_string() )
             },
+            "numeric" | "decimal" => {
+                (vec!["rustorm::decimal::Decimal".to_string()], "Decimal".to_string() )
+            },
             "name" | "character" | "character varying" | "text" | "citext" =>{
                 ( vec![], "String".to_string() )
             },
             "bytea" =>{
                 ( vec![], "Vec<u8>".to_string() )
             },
-            //"json" | "jsonb" => {
-            //    ((Some(vec!["rustc_serialize::json::Json".to_string()]), "Json".to_string()))
-           // },
-            "json" | "jsonb" => {//FIXME :String for now, since Json itself is not encodable
-                ((vec![], "String".to_string()))
+            "json" | "jsonb" => {
+                (vec!["rustorm::dao::JsonValue".to_string()], "JsonValue".to_string())
             },
             "uuid" => {
                 (vec!["uuid::Uuid".to_string()], "Uuid".to_string() )
@@ -679,73 +1255,291 @@ impl DatabaseDev for Postgres{
             "hstore" => {
                 (vec!["std::collections::HashMap".to_string()], "HashMap<String, Option<String>>".to_string())
             },
-            _ => panic!("Unable to get the equivalent data type for {}", db_type),
-        };
-        db_type
-    }
-
-    ///
-    /// convert rust data type names to database data type names
-    /// will be used in generating SQL for table creation
-    /// FIXME, need to restore the exact data type as before
-    fn rust_type_to_dbtype(&self, rust_type: &str, db_data_type:&str)->String{
-
-        let rust_type = match rust_type{
-            "bool" => {
-                "boolean".to_string()
-            },
-            "i8" => {
-                "char".to_string()
-            },
-            "i16" => {
-                "smallint".to_string()
-            },
-            "i32"  => {
-                "integer".to_string()
-            },
-            "u32"  => {
-                "oid".to_string()
+            "interval" => {
+                (vec!["rustorm::types::Interval".to_string()], "Interval".to_string())
             },
-            "i64"  => {
-                "bigint".to_string()
+            "inet" | "cidr" => {
+                (vec!["rustorm::types::Inet".to_string()], "Inet".to_string())
             },
-            "f32" => {
-                "real".to_string()
+            "macaddr" => {
+                (vec!["rustorm::types::MacAddr".to_string()], "MacAddr".to_string())
             },
-            "f64" => {
-                "numeric".to_string()
+            "money" => {
+                (vec!["rustorm::types::Money".to_string()], "Money".to_string())
             },
-            "String" =>{
-                "character varying".to_string()
+            "point" => {
+                (vec!["rustorm::types::Point".to_string()], "Point".to_string())
             },
-            "Vec<u8>" =>{
-                "bytea".to_string()
-            },
-            "Json" => {
-                "json".to_string()
-            },
-            "Uuid" => {
-                "uuid".to_string()
-            },
-            "NaiveDateTime" => {
-                "timestamp".to_string()
-            },
-            "DateTime<UTC>" => {
-                "timestamp with time zone".to_string()
-            },
-            "NaiveDate" => {
-                "date".to_string()
-            },
-            "NaiveTime" => {
-                "time".to_string()
+            "int4range" | "int8range" | "numrange" | "tsrange" | "tstzrange" | "daterange" => {
+                let element = match db_type{
+                    "int4range" => "integer",
+                    "int8range" => "bigint",
+                    "numrange" => "numeric",
+                    "tsrange" => "timestamp without time zone",
+                    "tstzrange" => "timestamp with time zone",
+                    _ => "date",
+                };
+                let (mut imports, element) = self.dbtype_to_rust_type(element);
+                imports.push("rustorm::types::Range".to_string());
+                (imports, format!("Range<{}>", element))
             },
-            "HashMap<String, Option<String>>" => {
-                "hstore".to_string()
+            _ => {
+                match self.find_user_type(db_type){
+                    Some(UserType{kind: UserTypeKind::Domain(ref base), ..}) => {
+                        let base = match base.find('('){
+                            Some(i) => &base[..i],
+                            None => &base[..],
+                        };
+                        self.dbtype_to_rust_type(base)
+                    },
+                    Some(user_type) => (vec![user_type.import_path()], user_type.type_name()),
+                    None => panic!("Unable to get the equivalent data type for {}", db_type),
+                }
             },
-            _ => panic!("Unable to get the equivalent database data type for {}", rust_type),
         };
-        rust_type
+        db_type
+    }
 
+    ///
+    /// convert rust data type names to database data type names
+    /// will be used in generating SQL for table creation
+    /// FIXME, need to restore the exact data type as before
+    fn rust_type_to_dbtype(&self, rust_type: &str, _db_data_type:&str)->String{
+        match pg_rust_type::db_type(rust_type){
+            Some(db_type) => db_type,
+            None => panic!("Unable to get the equivalent database data type for {}", rust_type),
+        }
     }
 
 }
+
+#[test]
+fn test_build_delete_using(){
+    use query::Equality;
+    let pg = Postgres::new();
+    let mut query = Query::delete();
+    query.from_table("bazaar.product_category")
+        .using(&"bazaar.category", "product_category.category_id", "category.category_id")
+        .filter("category.name", Equality::LIKE, &"Test%")
+        .return_all();
+    let sql_frag = pg.build_query(&query);
+    let expected = "DELETE FROM bazaar.product_category\n    USING bazaar.category\n    WHERE product_category.category_id = category.category_id \n        AND category.name LIKE $1 RETURNING *";
+    assert_eq!(sql_frag.sql, expected.to_string());
+}
+
+#[test]
+fn test_build_count_query(){
+    use query::Equality;
+    let pg = Postgres::new();
+    let mut query = Query::select_all();
+    query.from_table("bazaar.product")
+        .filter("name", Equality::LIKE, &"iphone%")
+        .desc("created")
+        .set_page(2);
+    let sql = pg.build_query(&query).sql;
+    assert!(sql.contains("LIMIT 20"));
+    assert!(sql.contains("OFFSET 40"));
+    let count = pg.build_query(&query.count_query());
+    assert!(count.sql.starts_with("SELECT COUNT(*) AS count"));
+    assert!(count.sql.contains("WHERE name LIKE $1"));
+    assert!(!count.sql.contains("ORDER BY"));
+    assert!(!count.sql.contains("LIMIT"));
+    query.set_distinct();
+    let distinct = pg.build_query(&query.count_query());
+    assert!(distinct.sql.contains("FROM (SELECT DISTINCT"));
+    assert!(distinct.sql.ends_with(") AS counted"));
+    assert_eq!(distinct.params.len(), 1);
+}
+
+#[test]
+fn test_build_seek_filter(){
+    use dao::Dao;
+    let pg = Postgres::new();
+    let mut last = Dao::new();
+    last.set("created", &"2015-01-01");
+    last.set("product_id", &10);
+    let mut query = Query::select_all();
+    query.from_table("bazaar.product")
+        .asc("created")
+        .asc("product_id");
+    let seek = query.seek_filter(&last).unwrap();
+    query.add_filter(seek);
+    let sql_frag = pg.build_query(&query);
+    assert!(sql_frag.sql.contains("WHERE ( created > $1 OR ( created = $2 AND product_id > $3"));
+    assert_eq!(sql_frag.params.len(), 3);
+}
+
+#[test]
+fn test_seek_filter_renamed_and_null(){
+    use dao::Dao;
+    let mut query = Query::select();
+    query.from_table("bazaar.product")
+        .left_join(&"bazaar.product_category", "product_category.product_id", "product.product_id")
+        .left_join(&"bazaar.category", "category.category_id", "product_category.category_id")
+        .enumerate_columns(vec!["product.product_id", "product.name", "category.name"])
+        .asc("category.name")
+        .asc("product.product_id")
+        .finalize();
+    let mut last = Dao::new();
+    last.set("product_id", &10);
+    last.set("name", &"iphone");
+    last.set("category.name", &"phones");
+    let mut seek = query.clone();
+    seek.add_filter(query.seek_filter(&last).unwrap());
+    let sql_frag = Postgres::new().build_query(&seek);
+    assert!(sql_frag.sql.contains("( category.name > $1 OR ( category.name = $2 AND product.product_id > $3"));
+    assert_eq!(sql_frag.params, vec![Type::String("phones".to_string()), 
+        Type::String("phones".to_string()), Type::I32(10)]);
+    // a NULL key would end the paging silently
+    last.set_value("category.name", Type::Null);
+    assert!(query.seek_filter(&last).is_err());
+    // the order_by column is not in the record
+    let mut unselected = Dao::new();
+    unselected.set("product_id", &10);
+    assert!(query.seek_filter(&unselected).is_err());
+    assert!(Query::select().seek_filter(&unselected).is_err());
+}
+
+#[test]
+fn test_build_json_path(){
+    use query::{Filter, Equality, JsonPath};
+    let pg = Postgres::new();
+    let mut query = Query::select();
+    query.from_table("bazaar.product")
+        .enumerate_column("name")
+        .enumerate_json(JsonPath::path_text("info", vec!["dimension", "weight"]), "weight")
+        .enumerate_json(JsonPath::get_index("tags", 0), "first_tag")
+        .enumerate_json(JsonPath::get("sales", "2015"), "sales_2015")
+        .add_filter(Filter::json(JsonPath::get_text("info", "brand"), Equality::EQ, &"apple"));
+    let sql_frag = pg.build_query(&query);
+    assert!(sql_frag.sql.contains("info #>> '{\"dimension\",\"weight\"}' AS weight"));
+    assert!(sql_frag.sql.contains("info ->> 'brand' = $1"));
+    assert!(sql_frag.sql.contains("tags -> 0 AS first_tag"));
+    assert!(sql_frag.sql.contains("sales -> '2015' AS sales_2015"));
+    assert_eq!(pg.dbtype_to_rust_type("jsonb").1, "JsonValue");
+}
+
+#[test]
+fn test_build_array_filters(){
+    use query::{Filter, Equality};
+    let pg = Postgres::new();
+    let mut query = Query::select_all();
+    query.from_table("bazaar.product")
+        .filter("product_id", Equality::ANY, &vec!["a", "b"])
+        .filter("tags", Equality::CONTAINS, &vec!["phone"])
+        .add_filter(Filter::new("category", Equality::NOTANY, &vec!["tablet"]));
+    let sql_frag = pg.build_query(&query);
+    assert!(sql_frag.sql.contains("product_id = ANY($1)"));
+    assert!(sql_frag.sql.contains("tags @> $2"));
+    assert!(sql_frag.sql.contains("category != ALL($3)"));
+    assert_eq!(pg.dbtype_to_rust_type("uuid[]").1, "Vec<Uuid>");
+    assert_eq!(pg.dbtype_to_rust_type("character varying[]").1, "Vec<String>");
+    assert_eq!(pg.rust_type_to_dbtype("Vec<i32>", ""), "integer[]");
+    assert_eq!(pg.rust_type_to_dbtype("Vec<u8>", ""), "bytea");
+}
+
+#[test]
+fn test_numeric_type_mapping(){
+    let pg = Postgres::new();
+    assert_eq!(pg.dbtype_to_rust_type("numeric"), (vec!["rustorm::decimal::Decimal".to_string()], "Decimal".to_string()));
+    assert_eq!(pg.dbtype_to_rust_type("double precision").1, "f64");
+    assert_eq!(pg.rust_type_to_dbtype("Decimal", "numeric"), "numeric");
+    assert_eq!(pg.rust_type_to_dbtype("f64", ""), "double precision");
+}
+
+#[test]
+fn test_user_type_source_code(){
+    let pg = Postgres::new();
+    let status = UserType{
+        schema: "bazaar".to_string(),
+        name: "product_status".to_string(),
+        kind: UserTypeKind::Enum(vec!["on sale".to_string(), "sold out".to_string()]),
+        comment: None,
+    };
+    let email = UserType{
+        schema: "bazaar".to_string(),
+        name: "email".to_string(),
+        kind: UserTypeKind::Domain("character varying(100)".to_string()),
+        comment: None,
+    };
+    *pg.user_types.borrow_mut() = Some(vec![status.clone(), email.clone()]);
+    assert_eq!(pg.dbtype_to_rust_type("bazaar.product_status"), 
+        (vec!["gen::bazaar::ProductStatus".to_string()], "ProductStatus".to_string()));
+    assert_eq!(pg.dbtype_to_rust_type("email").1, "String");
+    assert_eq!(pg.dbtype_to_rust_type("product_status[]").1, "Vec<ProductStatus>");
+
+    let (imports, src) = pg.to_user_type_source_code(&status);
+    assert!(imports.contains(&"rustorm::dao::ToType".to_string()));
+    assert!(src.contains("pub enum ProductStatus {"));
+    assert!(src.contains("ProductStatus::SoldOut => \"sold out\","));
+    assert!(src.contains("\"on sale\" => Ok(ProductStatus::OnSale),"));
+    assert!(pg.to_user_type_source_code(&email).1.is_empty());
+}
+
+#[test]
+fn test_range_filters(){
+    use query::{Filter, Equality};
+    use types::Range;
+    use chrono::naive::date::NaiveDate;
+    let pg = Postgres::new();
+    let mut query = Query::select_all();
+    query.from_table("bazaar.promo")
+        .filter("valid", Equality::CONTAINS, &NaiveDate::from_ymd(2015, 12, 24))
+        .add_filter(Filter::new("valid", Equality::OVERLAPS, &Range::new(NaiveDate::from_ymd(2015, 12, 1), NaiveDate::from_ymd(2016, 1, 1))));
+    let sql_frag = pg.build_query(&query);
+    assert!(sql_frag.sql.contains("valid @> $1::date"));
+    assert!(sql_frag.sql.contains("valid && $2"));
+    assert_eq!(pg.dbtype_to_rust_type("daterange").1, "Range<NaiveDate>");
+    assert_eq!(pg.dbtype_to_rust_type("tsrange").1, "Range<NaiveDateTime>");
+    assert_eq!(pg.dbtype_to_rust_type("cidr").1, "Inet");
+    assert_eq!(pg.dbtype_to_rust_type("interval"), (vec!["rustorm::types::Interval".to_string()], "Interval".to_string()));
+    assert_eq!(pg.rust_type_to_dbtype("Range<NaiveDate>", ""), "daterange");
+    assert_eq!(pg.rust_type_to_dbtype("MacAddr", ""), "macaddr");
+}
+
+#[test]
+fn test_rename_joined_columns(){
+    let pg = Postgres::new();
+    let mut query = Query::select();
+    query.from_table("bazaar.product")
+        .left_join(&"bazaar.product_category", "product_category.product_id", "product.product_id")
+        .left_join(&"bazaar.category", "category.category_id", "product_category.category_id")
+        .enumerate_columns(vec!["product.product_id", "product.name", "category.name", "category.category_id"])
+        .enumerate_column_as("category.description", "category_description")
+        .finalize();
+    let sql = pg.build_query(&query).sql;
+    assert!(sql.contains("product.name, category.name AS \"category.name\", category.category_id, "));
+    assert!(sql.contains("category.description AS category_description"));
+    assert_eq!(query.renamed_columns.get("category"), Some(&vec![
+        ("name".to_string(), "category.name".to_string()),
+    ]));
+    assert!(query.renamed_columns.get("product").is_none());
+    // finalizing again leaves the renamed columns as is
+    query.finalize();
+    assert_eq!(query.renamed_columns.get("category").unwrap().len(), 1);
+}
+
+#[test]
+fn test_result_column_names(){
+    assert_eq!(Postgres::result_column_names(&["id", "name", "name", "name_2"]),
+        vec!["id", "name", "name_3", "name_2"]);
+    assert_eq!(Postgres::result_column_names(&["name", "name", "name"]),
+        vec!["name", "name_1", "name_2"]);
+}
+
+#[test]
+fn test_relation_source_code(){
+    use table::fixture::{table, column};
+    let pg = Postgres::new();
+    let users = table("users", vec![column("user_id", true, None)]);
+    let product = table("product", vec![column("product_id", true, None),
+        column("owner_id", false, Some(("users", "user_id"))),
+        ]);
+    let tables = vec![users, product];
+    let (imports, _, src) = pg.to_struct_source_code(&tables[1], &tables);
+    assert!(imports.contains(&"rustorm::dao::HasRelation".to_string()));
+    assert!(src.contains("impl HasRelation for Product {"));
+    assert!(src.contains("\"owner\" => self.owner = dao.first().map(Users::from_dao),"));
+    let (_, _, src) = pg.to_struct_source_code(&tables[0], &tables);
+    assert!(src.contains("\"product\" => self.product = dao.iter().map(Product::from_dao).collect(),"));
+}
@@ -0,0 +1,174 @@
+use postgres::{Connection, Statement};
+use postgres::error::Error as PgError;
+
+/// the number of prepared statements kept per connection when not specified
+pub const DEFAULT_STATEMENT_CACHE_SIZE: usize = 100;
+
+/// sqlstate of the errors which means the cached statement is no longer valid,
+/// 0A000 cached plan must not change result type, raised after the table is altered,
+/// 26000 prepared statement does not exist, 42703 undefined column, 42804 datatype mismatch
+const STALE_STATEMENT_ERRORS: [&'static str; 4] = ["0A000", "26000", "42703", "42804"];
+
+/// the statistics of the statement cache
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(Default)]
+#[derive(PartialEq)]
+pub struct CacheStats{
+    /// the statement was already prepared
+    pub hits: u64,
+    /// the statement needs to be prepared
+    pub misses: u64,
+    /// the least recently used statements closed to keep the cache within the size limit
+    pub evictions: u64,
+    /// the statements closed after the schema has changed
+    pub invalidations: u64,
+}
+
+impl CacheStats{
+
+    /// the ratio of the hits over all the lookups, 0 when there is no lookup yet
+    pub fn hit_rate(&self)->f64{
+        let total = self.hits + self.misses;
+        if total == 0{
+            0.0
+        }else{
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+/// An LRU cache of the statements prepared with `Connection::prepare_cached` on 1 connection,
+/// keyed by the sql, the statements themselves are kept by the connection.
+/// The sql is prepared with a numbered comment in front of it, so the statement of an evicted
+/// or invalidated sql is closed on the server with DEALLOCATE, and the sql is prepared
+/// as a new statement when it is used again.
+/// The connection still keeps the description of the closed statements, which is not sent again
+pub struct StatementCache{
+    capacity: usize,
+    /// the sql and the text of its statement, the least recently used first
+    statements: Vec<(String, String)>,
+    /// the number of the last prepared statement
+    prepared: u64,
+    stats: CacheStats,
+}
+
+impl StatementCache{
+
+    /// a capacity of 0 disables the cache
+    pub fn new(capacity:usize)->Self{
+        StatementCache{
+            capacity: capacity,
+            statements: vec![],
+            prepared: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// only data manipulation statements are cached,
+    /// DDL and cursor statements are usually executed only once
+    pub fn is_cacheable(&self, sql:&str)->bool{
+        if self.capacity == 0{
+            return false;
+        }
+        let sql = sql.trim_left().to_uppercase();
+        ["SELECT", "INSERT", "UPDATE", "DELETE", "WITH"].iter().any(|k| sql.starts_with(k))
+    }
+
+    fn position(&self, sql:&str)->Option<usize>{
+        self.statements.iter().position(|s| s.0 == sql)
+    }
+
+    /// prepare the statement of the sql, using the statement cached by the connection when there is,
+    /// the least recently used statement is closed when the cache is full
+    pub fn prepare<'a>(&mut self, conn:&'a Connection, sql:&str)->Result<Statement<'a>, PgError>{
+        if !self.is_cacheable(sql){
+            return conn.prepare(sql);
+        }
+        match self.position(sql){
+            Some(index) => {
+                self.stats.hits += 1;
+                let entry = self.statements.remove(index);
+                let stmt = conn.prepare_cached(&entry.1);
+                self.statements.push(entry);
+                stmt
+            },
+            None => {
+                self.stats.misses += 1;
+                if self.statements.len() >= self.capacity{
+                    let (_, text) = self.statements.remove(0);
+                    Self::close(conn, &text);
+                    self.stats.evictions += 1;
+                }
+                self.prepared += 1;
+                let text = format!("/* rustorm {} */ {}", self.prepared, sql);
+                let stmt = try!(conn.prepare_cached(&text));
+                self.statements.push((sql.to_string(), text));
+                Ok(stmt)
+            },
+        }
+    }
+
+    /// deallocate the statements prepared from the text, the connection only knows them by name,
+    /// failing to close is ignored, such as when the transaction is aborted
+    fn close(conn:&Connection, text:&str){
+        let sql = "SELECT name FROM pg_prepared_statements WHERE statement = $1 AND NOT from_sql";
+        let rows = match conn.query(sql, &[&text]){
+            Ok(rows) => rows,
+            Err(_) => return,
+        };
+        for row in &rows{
+            let name:String = row.get(0);
+            let _ = conn.batch_execute(&format!("DEALLOCATE \"{}\"", name));
+        }
+    }
+
+    /// close the cached statement when the error means the statement is no longer valid,
+    /// returns true when the statement is closed, so the sql is cached again on the next prepare
+    pub fn invalidate(&mut self, conn:&Connection, sql:&str, error:&PgError)->bool{
+        let stale = match *error{
+            PgError::DbError(ref e) => STALE_STATEMENT_ERRORS.iter().any(|c| *c == e.code().code()),
+            _ => false,
+        };
+        match self.position(sql){
+            Some(index) if stale => {
+                let (_, text) = self.statements.remove(index);
+                Self::close(conn, &text);
+                self.stats.invalidations += 1;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    pub fn len(&self)->usize{
+        self.statements.len()
+    }
+
+    pub fn is_empty(&self)->bool{
+        self.statements.is_empty()
+    }
+
+    pub fn stats(&self)->CacheStats{
+        self.stats.clone()
+    }
+}
+
+#[test]
+fn test_hit_rate(){
+    let mut stats = CacheStats::default();
+    assert_eq!(stats.hit_rate(), 0.0);
+    stats.hits = 3;
+    stats.misses = 1;
+    assert_eq!(stats.hit_rate(), 0.75);
+}
+
+#[test]
+fn test_is_cacheable(){
+    let cache = StatementCache::new(10);
+    assert!(cache.is_cacheable("SELECT * FROM bazaar.product"));
+    assert!(cache.is_cacheable("\n  insert into bazaar.product"));
+    assert!(!cache.is_cacheable("FETCH FORWARD 10 FROM rustorm_cursor_1"));
+    assert!(!cache.is_cacheable("CREATE TABLE bazaar.product"));
+    assert!(!StatementCache::new(0).is_cacheable("SELECT 1"));
+}
@@ -2,11 +2,14 @@ use dao::{Type, ToType};
 use table::{Table, Column};
 use std::collections::BTreeMap;
 use database::Database;
+use cursor::{Cursor, EntityCursor};
 use dao::DaoResult;
 use dao::IsDao;
 use dao::Dao;
 use table::IsTable;
 use writer::SqlFrag;
+use dao_serde::{self, DaoError};
+use serde::de::DeserializeOwned;
 
 #[derive(Debug)]
 #[derive(Clone)]
@@ -65,6 +68,12 @@ pub enum Equality{
     NULL,
     NOTNULL,//NOT_NULL,
     ISNULL,//IS_NULL,
+    ANY, // = ANY(array), equal to any of the array elements
+    ALL, // = ALL(array), equal to all of the array elements
+    NOTANY, // != ALL(array), not equal to any of the array elements
+    CONTAINS, // @> the array or range contains the other array, range or the range element
+    CONTAINED, // <@ the array or range is contained in the other array or range
+    OVERLAPS, // && the arrays or ranges have elements in common
 }
 
 /// function in a sql statement
@@ -75,6 +84,78 @@ pub struct Function{
     pub params:Vec<Operand>,
 }
 
+/// the operators for extracting a value out of a json or jsonb column
+#[derive(Debug)]
+#[derive(Clone)]
+pub enum JsonOperator{
+    GET, // -> the field or the array element as json
+    GETTEXT, // ->> the field or the array element as text
+    PATH, // #> the value at the path as json
+    PATHTEXT, // #>> the value at the path as text
+}
+
+/// an element of a json path, the field of an object or the element of an array
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum JsonKey{
+    /// the field name, even when it looks like a number ie: `info -> '2015'`
+    Field(String),
+    /// the array index, negative counts from the end ie: `tags -> 0`
+    Index(i32),
+}
+
+/// a value extracted from a json column ie: `product.info ->> 'brand'`
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct JsonPath{
+    pub column:ColumnName,
+    pub operator:JsonOperator,
+    /// the field names or array indexes, GET and GETTEXT only use the first one
+    pub path:Vec<JsonKey>,
+}
+
+impl JsonPath{
+
+    pub fn new(column:&str, operator:JsonOperator, path:Vec<JsonKey>)->Self{
+        JsonPath{
+            column: ColumnName::from_str(column),
+            operator: operator,
+            path: path,
+        }
+    }
+
+    /// `column -> 'key'`
+    pub fn get(column:&str, key:&str)->Self{
+        JsonPath::new(column, JsonOperator::GET, vec![JsonKey::Field(key.to_string())])
+    }
+
+    /// `column -> index`
+    pub fn get_index(column:&str, index:i32)->Self{
+        JsonPath::new(column, JsonOperator::GET, vec![JsonKey::Index(index)])
+    }
+
+    /// `column ->> 'key'`
+    pub fn get_text(column:&str, key:&str)->Self{
+        JsonPath::new(column, JsonOperator::GETTEXT, vec![JsonKey::Field(key.to_string())])
+    }
+
+    /// `column ->> index`
+    pub fn get_text_index(column:&str, index:i32)->Self{
+        JsonPath::new(column, JsonOperator::GETTEXT, vec![JsonKey::Index(index)])
+    }
+
+    /// `column #> '{key1,key2}'`, postgresql reads a key as an array index when the value is an array
+    pub fn path(column:&str, path:Vec<&str>)->Self{
+        JsonPath::new(column, JsonOperator::PATH, path.iter().map(|p| JsonKey::Field(p.to_string())).collect())
+    }
+
+    /// `column #>> '{key1,key2}'`
+    pub fn path_text(column:&str, path:Vec<&str>)->Self{
+        JsonPath::new(column, JsonOperator::PATHTEXT, path.iter().map(|p| JsonKey::Field(p.to_string())).collect())
+    }
+}
+
 /// Operands can be columns, functions, query or value types
 #[derive(Debug)]
 #[derive(Clone)]
@@ -85,6 +166,7 @@ pub enum Operand{
     Query(Query),
     Value(Type),
     Vec(Vec<Operand>),
+    JsonPath(JsonPath),
 }
 
 /// expression has left operand,
@@ -122,6 +204,38 @@ impl Filter{
         }
     }
     
+    /// filter on the value extracted from a json column ie: `info ->> 'brand' = $1`
+    pub fn json(json:JsonPath, equality:Equality, value:&ToType)->Self{
+        Filter{
+            connector:Connector::And,
+            condition: Condition{left_operand: Operand::JsonPath(json),
+                        equality:equality,
+                        right_operand:Operand::Value(value.to_db_type())},
+            subfilters:vec![],
+        }
+    }
+    
+    /// filter the column which has no value, ie: `column IS NULL`
+    pub fn is_null(column:&str)->Self{
+        Filter::new(column, Equality::ISNULL, &Type::Null)
+    }
+
+    /// filter the column which matches any of the values, ie: `column IN (v1, v2, ..)`
+    pub fn in_values(column:&str, values:Vec<Type>)->Self{
+        let mut operands = vec![];
+        for v in values{
+            operands.push(Operand::Value(v));
+        }
+        Filter{
+            connector:Connector::And,
+            condition: Condition{left_operand:
+                        Operand::ColumnName(ColumnName::from_str(column)),
+                        equality:Equality::IN,
+                        right_operand:Operand::Vec(operands)},
+            subfilters:vec![],
+        }
+    }
+    
     pub fn and(&mut self, column:&str, equality:Equality, value:&ToType)->&mut Self{
         let mut filter = Filter::new(column, equality, value);
         filter.connector = Connector::And;
@@ -138,6 +252,9 @@ impl Filter{
     
 }
 
+/// the page size used when the page is set without the page size
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+
 /// Could have been SqlAction
 #[derive(Debug)]
 #[derive(Clone)]
@@ -323,7 +440,7 @@ pub struct Query{
     pub enumerated_fields:Vec<Field>,
     
     /// list of renamed columns whenever there is a conflict
-    /// Vec(table, column, new_column_name)
+    /// table => Vec(column, new_column_name)
     pub renamed_columns:BTreeMap<String, Vec<(String, String)>>,
     
     /// specify to use distinct ON set of columns 
@@ -444,6 +561,21 @@ impl Query{
         self
     }
     
+    /// enumerate a column and rename it in the result
+    pub fn enumerate_column_as(&mut self, column:&str, rename:&str)->&mut Self{
+        let column_name = ColumnName::from_str(column);
+        let operand = Operand::ColumnName(column_name);
+        let field = Field{operand:operand, name:Some(rename.to_string())};
+        self.enumerated_fields.push(field);
+        self
+    }
+    
+    /// enumerate a value extracted from a json column, named as rename in the result
+    pub fn enumerate_json(&mut self, json:JsonPath, rename:&str)->&mut Self{
+        let field = Field{operand:Operand::JsonPath(json), name:Some(rename.to_string())};
+        self.enumerated_fields.push(field);
+        self
+    }
     
     pub fn enumerate_columns(&mut self, columns:Vec<&str>)->&mut Self{
         for c in columns{
@@ -517,6 +649,15 @@ impl Query{
         self.set_page_size(limit)
     }
     
+    /// the page size, DEFAULT_PAGE_SIZE is used when only the page is specified
+    pub fn get_page_size(&self)->Option<usize>{
+        match (self.page_size, self.page){
+            (Some(page_size), _) => Some(page_size),
+            (None, Some(_)) => Some(DEFAULT_PAGE_SIZE),
+            (None, None) => None,
+        }
+    }
+    
     /// A more terse way to write the query
     pub fn from(&mut self, table: &ToTableName)->&mut Self{
         let table_name = table.to_table_name();
@@ -661,6 +802,22 @@ impl Query{
         self.join(join)
     }
     
+    /// join a table when deleting records, this is written as `DELETE ... USING table`
+    /// the join columns are then used as conditions in the WHERE clause
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut q = Query::delete();
+    /// q.from_table("bazaar.product_category");
+    /// q.using(&"bazaar.category", "product_category.category_id", "category.category_id");
+    /// q.filter("category.name", Equality::LIKE, &"Test%");
+    ///
+    /// ```
+    pub fn using(&mut self, table:&ToTableName, column1:&str, column2:&str)->&mut Self{
+        self.inner_join(table, column1, column2)
+    }
+    
     ///ascending orderby of this column
     pub fn asc(&mut self, column:&str)->&mut Self{
         self.order_by.push((column.to_string(), Direction::ASC));
@@ -673,19 +830,15 @@ impl Query{
     }
     
     
+    /// record that the column of the table is named new_column_name in the result
     pub fn rename(&mut self, table:&str, column:&str, new_column_name:&str)->&mut Self{
-        if self.renamed_columns.get(table).is_some(){
-            let mut list:&mut Vec<(String, String)> = self.renamed_columns.get_mut(table).unwrap();
-            if list.contains(&(column.to_string(), new_column_name.to_string())){
-                println!("This is already renamed");
-            }else{
-                println!("renamed {} to {}", column, new_column_name);
-                list.push((column.to_string(), new_column_name.to_string()));
+        let renamed = (column.to_string(), new_column_name.to_string());
+        {
+            let list = self.renamed_columns.entry(table.to_string()).or_insert(vec![]);
+            if !list.contains(&renamed){
+                list.push(renamed);
             }
         }
-        else{
-            self.renamed_columns.insert(table.to_string(), vec![(column.to_string(), new_column_name.to_string())]);
-        }
         self
     }
     
@@ -708,7 +861,7 @@ impl Query{
     /// enumerate the columns of the involved tables
     /// skipping those which are explicitly ignored
     /// the query will then be built and ready to be executed
-    /// TODO: renamed conflicting enumerated columns
+    /// the enumerated columns of the joined tables are renamed when their names conflict
     /// if no enumerated fields and no excluded columns
     /// do a select all
     pub fn finalize(&mut self)->&mut Self{
@@ -720,9 +873,56 @@ impl Query{
         for i in  excluded_columns{
             self.remove_from_enumerated(&i);
         }
+        match self.sql_type{
+            SqlType::SELECT => self.rename_joined_columns(),
+            _ => (),
+        }
         self
     }
     
+    /// the name of the field in the result, None when it can not be known from the query
+    fn result_name(field:&Field)->Option<&str>{
+        match field.name{
+            Some(ref name) => Some(&name[..]),
+            None => match field.operand{
+                Operand::ColumnName(ref column_name) if column_name.column != "*" => Some(&column_name.column[..]),
+                _ => None,
+            },
+        }
+    }
+
+    /// rename the enumerated columns of the joined tables which have the same name
+    /// as another enumerated column to their table qualified name,
+    /// ie: `category.name AS "category.name"`, so they don't overwrite `product.name`.
+    /// The renamed columns are recorded in renamed_columns,
+    /// the columns which are already renamed and `table.*` are left as is
+    fn rename_joined_columns(&mut self){
+        let joined_tables:Vec<String> = self.joins.iter().map(|j| j.table_name.name.to_string()).collect();
+        let names:Vec<String> = self.enumerated_fields.iter()
+            .filter_map(|f| Self::result_name(f).map(|n| n.to_string()))
+            .collect();
+        let mut renamed = vec![];
+        for field in &mut self.enumerated_fields{
+            if field.name.is_some(){
+                continue;
+            }
+            if let Operand::ColumnName(ref column_name) = field.operand{
+                let conflicts = names.iter().filter(|n| **n == column_name.column).count() > 1;
+                match column_name.table{
+                    Some(ref table) if joined_tables.contains(table) && conflicts => {
+                        let new_column_name = column_name.complete_name();
+                        field.name = Some(new_column_name.to_string());
+                        renamed.push((table.to_string(), column_name.column.to_string(), new_column_name));
+                    },
+                    _ => (),
+                }
+            }
+        }
+        for (table, column, new_column_name) in renamed{
+            self.rename(&table, &column, &new_column_name);
+        }
+    }
+    
     fn remove_from_enumerated(&mut self, column_name: &ColumnName)->&mut Self{
         fn index_of(enumerated_fields:&Vec<Field>, column: &ColumnName)->Option<usize>{
             let mut cnt = 0;
@@ -822,7 +1022,7 @@ impl Query{
     }
     
     /// expects a return, such as select, insert/update with returning clause
-    fn execute_with_return(&mut self, db: &Database)->DaoResult{
+    pub fn execute_with_return(&mut self, db: &Database)->DaoResult{
         self.finalize();
         db.execute_with_return(self)
     }
@@ -845,6 +1045,16 @@ impl Query{
         T::from_dao_result(&result)
     }
     
+    /// execute the query, then deserialize the records into any struct which implements serde `Deserialize`
+    pub fn collect_deserialized<T: DeserializeOwned>(&mut self, db: &Database)->Result<Vec<T>, DaoError>{
+        let result = self.execute_with_return(db);
+        let mut records = vec![];
+        for dao in &result.dao{
+            records.push(try!(dao_serde::from_dao(dao)));
+        }
+        Ok(records)
+    }
+    
     /// execute the query then collect only 1 record
     /// put a limit 1 if not already
     pub fn collect_one<T: IsDao>(&mut self, db: &Database)->T{
@@ -856,4 +1066,243 @@ impl Query{
         assert!(dao.len() == 1, "There should only be 1 returned record");
         dao.remove(0)
     }
+    
+    /// execute the query which joins the table of R, then convert each record into T and R,
+    /// ie: `Vec<(Product, Option<ProductAvailability>)>` from a LEFT JOIN of product_availability.
+    /// The columns of R are the enumerated `R.column`, with the names given by finalize,
+    /// R is None when all of its columns are NULL, which is when there is no matching record.
+    /// The columns of both tables are enumerated when there are no enumerated columns
+    pub fn collect_joined<T, R>(&mut self, db: &Database)->Vec<(T, Option<R>)>
+        where T: IsDao + IsTable, R: IsDao + IsTable{
+        if self.enumerated_fields.is_empty(){
+            self.enumerate_table_all_columns(&T::table());
+            self.enumerate_table_all_columns(&R::table());
+        }
+        self.finalize();
+        let joined_table = R::table().name;
+        let joined_columns = self.joined_columns(&joined_table);
+        assert!(!joined_columns.is_empty(),
+            "The table {} should be joined, with its columns enumerated as {}.column", joined_table, joined_table);
+        let result = self.execute_with_return(db);
+        let mut records = vec![];
+        for dao in &result.dao{
+            let joined = Self::joined_dao(dao, &joined_columns);
+            records.push((T::from_dao(dao), joined.map(|d| R::from_dao(&d))));
+        }
+        records
+    }
+    
+    /// the enumerated columns of the joined table and their names in the result
+    fn joined_columns(&self, table:&str)->Vec<(String, String)>{
+        let mut joined_columns = vec![];
+        if !self.joins.iter().any(|j| j.table_name.name == table){
+            return joined_columns;
+        }
+        for field in &self.enumerated_fields{
+            if let Operand::ColumnName(ref column_name) = field.operand{
+                if column_name.table.as_ref().map(|t| &t[..]) == Some(table){
+                    if let Some(name) = Self::result_name(field){
+                        joined_columns.push((column_name.column.to_string(), name.to_string()));
+                    }
+                }
+            }
+        }
+        joined_columns
+    }
+    
+    /// the columns of the joined table in the record, using their names in the table,
+    /// None when all of them are NULL
+    fn joined_dao(dao:&Dao, joined_columns:&[(String, String)])->Option<Dao>{
+        let mut joined = Dao::new();
+        let mut all_null = true;
+        for &(ref column, ref new_column_name) in joined_columns{
+            match dao.values.get(new_column_name){
+                Some(value) => {
+                    if *value != Type::Null{
+                        all_null = false;
+                    }
+                    joined.set_value(column, value.clone());
+                },
+                None => (),
+            }
+        }
+        if all_null{ None }else{ Some(joined) }
+    }
+    
+    /// a query which counts the records this query would return without the paging,
+    /// the query is wrapped as a subquery when it is distinct or grouped
+    pub fn count_query(&self)->Query{
+        let mut query = self.clone();
+        query.order_by.clear();
+        query.page = None;
+        query.page_size = None;
+        let count = Field{
+            operand: Operand::Function(Function{
+                function: "COUNT".to_string(),
+                params: vec![Operand::ColumnName(ColumnName::from_str("*"))],
+            }),
+            name: Some("count".to_string()),
+        };
+        if query.distinct || !query.distinct_on_columns.is_empty() || !query.group_by.is_empty(){
+            query.finalize();
+            let mut outer = Query::select();
+            outer.enumerated_fields.push(count);
+            outer.from_query(query, "counted");
+            outer
+        }else{
+            query.enumerated_fields = vec![count];
+            query.excluded_columns.clear();
+            query.renamed_columns.clear();
+            query
+        }
+    }
+    
+    /// the total number of records this query would return without the paging
+    pub fn count(&self, db: &Database)->Result<usize, String>{
+        let sql_frag = db.build_query(&self.count_query());
+        let counted = try!(db.try_execute_sql_with_return(&sql_frag.sql, &sql_frag.params));
+        match counted.first().and_then(|dao| dao.values.get("count")){
+            Some(&Type::I64(count)) => Ok(count as usize),
+            Some(&Type::I32(count)) => Ok(count as usize),
+            count => Err(format!("Unexpected count {:?}", count)),
+        }
+    }
+    
+    /// retrieve the records of the page, also counting the total records,
+    /// the total, page and page size are set in the DaoResult
+    pub fn retrieve_page(&mut self, db: &Database)->Result<DaoResult, String>{
+        self.finalize();
+        let total = try!(self.count(db));
+        let mut result = db.execute_with_return(self);
+        result.total = Some(total);
+        result.page = Some(self.page.unwrap_or(0));
+        result.page_size = self.get_page_size();
+        Ok(result)
+    }
+    
+    /// retrieve the page of records, along with the total, page and page size
+    pub fn collect_page<T: IsDao>(&mut self, db: &Database)->Result<(Vec<T>, DaoResult), String>{
+        let result = try!(self.retrieve_page(db));
+        Ok((T::from_dao_result(&result), result))
+    }
+    
+    /// keyset pagination, a filter for the records that come after the last record
+    /// based on the order_by columns ie: `(created > $1 OR (created = $2 AND product_id > $3))`
+    /// the order_by columns should end with a unique column for the paging to be stable,
+    /// returns an error when the last record has no value or a NULL value for an order_by column
+    pub fn seek_filter(&self, last: &Dao)->Result<Filter, String>{
+        if self.order_by.is_empty(){
+            return Err(format!("There should be order_by columns for keyset pagination"));
+        }
+        let mut seek:Option<Filter> = None;
+        for (i, &(ref column, ref direction)) in self.order_by.iter().enumerate(){
+            let after = match *direction{
+                Direction::ASC => Equality::GT,
+                Direction::DESC => Equality::LT,
+            };
+            let mut filter = Filter::new(column, after, &try!(self.seek_value(last, column)));
+            if i > 0{
+                let first = &self.order_by[0].0;
+                let mut equal = Filter::new(first, Equality::EQ, &try!(self.seek_value(last, first)));
+                for &(ref previous, _) in &self.order_by[1..i]{
+                    equal.and(previous, Equality::EQ, &try!(self.seek_value(last, previous)));
+                }
+                filter.connector = Connector::And;
+                equal.subfilters.push(filter);
+                filter = equal;
+                filter.connector = Connector::Or;
+            }
+            match seek{
+                Some(ref mut seek) => seek.subfilters.push(filter),
+                None => seek = Some(filter),
+            }
+        }
+        Ok(seek.unwrap())
+    }
+    
+    /// the value of the order_by column in the last record,
+    /// a joined column which is renamed in the result ie: `category.name` is looked up by its new name,
+    /// a NULL value is rejected since `column > NULL` matches no records and would end the paging
+    fn seek_value(&self, last: &Dao, column: &str)->Result<Type, String>{
+        let column_name = ColumnName::from_str(column);
+        let renamed = match column_name.table{
+            Some(ref table) => self.renamed_columns.get(table)
+                .and_then(|renamed| renamed.iter().find(|&&(ref c, _)| *c == column_name.column))
+                .map(|&(_, ref new_column_name)| new_column_name.to_string()),
+            None => None,
+        };
+        let name = renamed.unwrap_or(column_name.column.to_string());
+        match last.values.get(&name){
+            Some(&Type::Null) => Err(format!("The order_by column {} of the last record is NULL, keyset pagination needs non NULL values", column)),
+            Some(value) => Ok(value.clone()),
+            None => Err(format!("The last record does not have the order_by column {}, it should be selected", column)),
+        }
+    }
+    
+    /// keyset pagination, retrieve the records after the last record of the previous page,
+    /// or the first page when there is no last record.
+    /// This is stable and fast for deep paging, since no records are skipped using OFFSET
+    pub fn retrieve_after(&mut self, db: &Database, last: Option<&Dao>)->Result<DaoResult, String>{
+        self.finalize();
+        let total = try!(self.count(db));
+        let mut query = self.clone();
+        query.page = None;
+        query.page_size = Some(self.page_size.unwrap_or(DEFAULT_PAGE_SIZE));
+        match last{
+            Some(last) => {
+                let seek = try!(query.seek_filter(last));
+                query.add_filter(seek);
+            },
+            None => (),
+        }
+        let mut result = db.execute_with_return(&query);
+        result.total = Some(total);
+        result.page = None;
+        result.page_size = query.page_size;
+        Ok(result)
+    }
+    
+    /// iterate over the records, fetching only fetch_size records at a time,
+    /// use this instead of collect when the result is too large to fit in memory,
+    /// returns an error when the cursor can not be declared
+    pub fn stream<'a>(&mut self, db: &'a Database, fetch_size: usize)->Result<Cursor<'a>, String>{
+        self.finalize();
+        Cursor::new(db, self, fetch_size)
+    }
+    
+    /// iterate over the records converted into the generated struct, fetching only fetch_size records at a time
+    pub fn stream_as<'a, T: IsDao>(&mut self, db: &'a Database, fetch_size: usize)->Result<EntityCursor<'a, T>, String>{
+        self.stream(db, fetch_size).map(EntityCursor::new)
+    }
+}
+
+#[test]
+fn test_joined_dao(){
+    let mut query = Query::select();
+    query.from_table("bazaar.product")
+        .left_join(&"bazaar.product_availability", "product_availability.product_id", "product.product_id")
+        .enumerate_columns(vec!["product.product_id", "product.name",
+            "product_availability.product_id", "product_availability.available", "product_availability.stocks"])
+        .finalize();
+    let joined_columns = query.joined_columns("product_availability");
+    assert_eq!(joined_columns, vec![
+        ("product_id".to_string(), "product_availability.product_id".to_string()),
+        ("available".to_string(), "available".to_string()),
+        ("stocks".to_string(), "stocks".to_string()),
+    ]);
+    assert!(query.joined_columns("category").is_empty());
+    let mut dao = Dao::new();
+    dao.set("product_id", &1);
+    dao.set("name", &"iphone");
+    dao.set("product_availability.product_id", &1);
+    dao.set("available", &true);
+    dao.set_value("stocks", Type::Null);
+    let joined = Query::joined_dao(&dao, &joined_columns).unwrap();
+    let columns:Vec<&str> = joined.values.keys().collect();
+    assert_eq!(columns, vec!["product_id", "available", "stocks"]);
+    assert_eq!(joined.get::<bool>("available"), true);
+    assert_eq!(joined.get_opt::<i32>("stocks"), None);
+    dao.set_value("product_availability.product_id", Type::Null);
+    dao.set_value("available", Type::Null);
+    assert!(Query::joined_dao(&dao, &joined_columns).is_none());
 }
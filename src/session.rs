@@ -0,0 +1,586 @@
+use query::{Query, Filter, Operand, Equality};
+use table::{Table, IsTable, Version};
+use dao::{Dao, IsDao, ToDao, Type, ToType};
+use database::Database;
+use em::EntityManager;
+use chrono::offset::utc::UTC;
+use chrono::datetime::DateTime;
+use chrono::Timelike;
+use std::fmt;
+
+/// the savepoint of the flush when the changes are written in an already open transaction
+const FLUSH_SAVEPOINT: &'static str = "rustorm_flush";
+
+/// errors when writing the changes of the session
+#[derive(Debug)]
+pub enum FlushError{
+    /// the record was updated or deleted by some other transaction since it was retrieved,
+    /// applicable only to tables with version column
+    Conflict{table:String, key:String},
+    /// error from the database
+    Database(String),
+}
+
+impl fmt::Display for FlushError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FlushError::Conflict{ref table, ref key} => {
+                write!(f, "Record {} of {} has been changed by another transaction", key, table)
+            },
+            FlushError::Database(ref e) => {
+                write!(f, "{}", e)
+            },
+        }
+    }
+}
+
+/// a tracked record of the session
+struct Entry{
+    table: Table,
+    /// the values of the record when it was loaded or last flushed,
+    /// None when the record is new
+    snapshot: Option<Dao>,
+    /// the current values of the record
+    dao: Dao,
+    /// the record will be deleted on flush
+    removed: bool,
+}
+
+impl Entry{
+
+    /// the primary key values of this record, None when the primary key is not set
+    fn key(&self)->Option<String>{
+        let dao = match self.snapshot{
+            Some(ref snapshot) => snapshot,
+            None => &self.dao,
+        };
+        Session::key_of(&self.table, dao)
+    }
+}
+
+/// Unit of work, keeps track of the changes of the entities
+/// then write only the changed columns to the database in 1 transaction
+/// # Examples
+/// ```rust,no_run
+/// let em = EntityManager::new(&pg);
+/// let mut session = em.session();
+/// let mut prod:Product = session.get_exact(&pid);
+/// prod.name = Some("iphone 6s".to_string());
+/// session.save(&prod);
+/// session.flush().unwrap();
+/// ```
+pub struct Session<'a>{
+    db: &'a Database,
+    entries: Vec<Entry>,
+    /// the entity manager which cached records are evicted when the changes are written
+    em: Option<&'a EntityManager<'a>>,
+}
+
+impl <'a>Session<'a>{
+
+    pub fn new(db:&'a Database)->Self{
+        Session{db: db, entries: vec![], em: None}
+    }
+
+    /// a session which evicts the written tables from the entity manager after each flush
+    pub fn with_entity_manager(em:&'a EntityManager<'a>)->Self{
+        Session{db: em.db, entries: vec![], em: Some(em)}
+    }
+
+    /// get an exact match using the primary key of the table,
+    /// the record is then tracked by this session
+    pub fn get_exact<T>(&mut self, id: &ToType)->T
+        where T : IsTable + IsDao{
+        let table = T::table();
+        let primary = table.primary_columns();
+        assert!(primary.len() == 1, "There should only be 1 primary column for this to work");
+        let pk = primary[0].name.to_string();
+
+        let mut q = Query::select();
+        q.from(&table);
+        q.enumerate_table_all_columns(&table);
+        q.filter(&pk, Equality::EQ, id);
+        q.limit(1);
+        let dao = q.execute_with_one_return(self.db);
+        let obj = T::from_dao(&dao);
+        self.track(table, Some(dao.clone()), dao);
+        obj
+    }
+
+    /// keep track of an entity which is already in the database
+    pub fn attach<T>(&mut self, obj:&T)
+        where T : IsTable + ToDao{
+        let dao = obj.to_dao();
+        self.track(T::table(), Some(dao.clone()), dao);
+    }
+
+    /// a new entity which will be inserted on flush
+    pub fn add<T>(&mut self, obj:&T)
+        where T : IsTable + ToDao{
+        self.track(T::table(), None, obj.to_dao());
+    }
+
+    /// set the changed values of the entity, the entity will be updated on flush
+    /// if the entity is not tracked yet, it will be inserted
+    pub fn save<T>(&mut self, obj:&T)
+        where T : IsTable + ToDao{
+        let table = T::table();
+        let dao = obj.to_dao();
+        match self.index_of(&table, &dao){
+            Some(index) => self.entries[index].dao = dao,
+            None => self.track(table, None, dao),
+        }
+    }
+
+    /// the entity will be deleted on flush
+    pub fn remove<T>(&mut self, obj:&T)
+        where T : IsTable + ToDao{
+        let table = T::table();
+        let dao = obj.to_dao();
+        let index = match self.index_of(&table, &dao){
+            Some(index) => index,
+            None => {
+                self.track(table, Some(dao.clone()), dao);
+                self.entries.len() - 1
+            }
+        };
+        self.entries[index].removed = true;
+    }
+
+    /// determine if there are changes not yet written to the database
+    pub fn is_dirty(&self)->bool{
+        for e in &self.entries{
+            if e.removed || e.snapshot.is_none(){
+                return true;
+            }
+            if !Self::changed_columns(e).is_empty(){
+                return true;
+            }
+        }
+        false
+    }
+
+    /// write the changes to the database in 1 transaction,
+    /// new records are inserted, only the changed columns are updated,
+    /// referred tables are written first before the tables referring to them,
+    /// and deleted last after them
+    /// when the table has a version column, a record that was changed by another transaction
+    /// is not overwritten, a Conflict error is returned instead and nothing is written.
+    /// When a transaction is already open, the changes are written in a savepoint of it
+    /// and the transaction is left open, nothing is sent when there are no changes
+    pub fn flush(&mut self)->Result<(), FlushError>{
+        if !self.is_dirty(){
+            return Ok(());
+        }
+        let nested = self.db.is_transacted();
+        try!(self.begin(nested).map_err(FlushError::Database));
+        match self.write_changes(){
+            Ok(written) => {
+                try!(self.commit(nested).map_err(FlushError::Database));
+                self.evict_written();
+                for (index, dao) in written{
+                    self.entries[index].dao = dao;
+                }
+                self.entries.retain(|e| !e.removed);
+                for e in &mut self.entries{
+                    e.snapshot = Some(e.dao.clone());
+                }
+                Ok(())
+            },
+            Err(e) => {
+                let _ = self.rollback(nested);
+                Err(e)
+            }
+        }
+    }
+
+    fn begin(&self, nested:bool)->Result<(), String>{
+        if nested{
+            self.db.execute_sql(&format!("SAVEPOINT {}", FLUSH_SAVEPOINT), &vec![]).map(|_| ())
+        }else{
+            self.db.begin()
+        }
+    }
+
+    fn commit(&self, nested:bool)->Result<(), String>{
+        if nested{
+            self.db.execute_sql(&format!("RELEASE SAVEPOINT {}", FLUSH_SAVEPOINT), &vec![]).map(|_| ())
+        }else{
+            self.db.commit()
+        }
+    }
+
+    fn rollback(&self, nested:bool)->Result<(), String>{
+        if nested{
+            self.db.execute_sql(&format!("ROLLBACK TO SAVEPOINT {}", FLUSH_SAVEPOINT), &vec![]).map(|_| ())
+        }else{
+            self.db.rollback()
+        }
+    }
+
+    /// evict the tables with changes from the entity manager,
+    /// since its cached records of those tables are now stale
+    fn evict_written(&self){
+        let em = match self.em{
+            Some(em) => em,
+            None => return,
+        };
+        let mut evicted:Vec<&Table> = vec![];
+        for e in &self.entries{
+            let is_written = e.removed || e.snapshot.is_none() || !Self::changed_columns(e).is_empty();
+            if is_written && !evicted.contains(&&e.table){
+                em.evict_table(&e.table);
+                evicted.push(&e.table);
+            }
+        }
+    }
+
+    /// forget all the tracked entities, changes not yet flushed are discarded
+    pub fn clear(&mut self){
+        self.entries.clear();
+    }
+
+    fn track(&mut self, table:Table, snapshot:Option<Dao>, dao:Dao){
+        let entry = Entry{table: table, snapshot: snapshot, dao: dao, removed: false};
+        match entry.key(){
+            Some(ref key) => {
+                let table_name = entry.table.complete_name();
+                self.entries.retain(|e| e.table.complete_name() != table_name || e.key().as_ref() != Some(key));
+            },
+            None => (),
+        }
+        self.entries.push(entry);
+    }
+
+    /// the index of the tracked record with the same primary key values
+    fn index_of(&self, table:&Table, dao:&Dao)->Option<usize>{
+        let key = Self::key_of(table, dao);
+        if key.is_none(){
+            return None;
+        }
+        let mut index = 0;
+        for e in &self.entries{
+            if &e.table == table && e.key() == key{
+                return Some(index);
+            }
+            index += 1;
+        }
+        None
+    }
+
+    /// the values of the primary columns, None if any of it is not set
+    fn key_of(table:&Table, dao:&Dao)->Option<String>{
+        let primary = table.primary_columns();
+        assert!(!primary.is_empty(), "There should be primary column to track {}", table.complete_name());
+        let mut key = String::new();
+        for p in primary{
+            match dao.values.get(&p.name){
+                Some(&Type::Null) | None => return None,
+                Some(value) => key.push_str(&format!("{:?};", value)),
+            }
+        }
+        Some(key)
+    }
+
+    /// the columns which values are different from the snapshot
+    /// the version column is not included, since it is set only when updating
+    fn changed_columns(entry:&Entry)->Vec<String>{
+        let mut changed = vec![];
+        let snapshot = match entry.snapshot{
+            Some(ref snapshot) => snapshot,
+            None => return changed,
+        };
+        let version = entry.table.version.as_ref().map(|v| v.column().to_string());
+        for c in &entry.table.columns{
+            if changed.contains(&c.name) || version.as_ref() == Some(&c.name){
+                continue;
+            }
+            match entry.dao.values.get(&c.name){
+                Some(value) => {
+                    if snapshot.values.get(&c.name) != Some(value){
+                        changed.push(c.name.to_string());
+                    }
+                },
+                None => (),
+            }
+        }
+        changed
+    }
+
+    /// execute the inserts, updates and deletes,
+    /// returns the index of the inserted and updated records and their new values
+    fn write_changes(&self)->Result<Vec<(usize, Dao)>, FlushError>{
+        let mut tables = vec![];
+        for e in &self.entries{
+            if !tables.contains(&e.table){
+                tables.push(e.table.clone());
+            }
+        }
+        let ordered = Self::dependency_order(&tables);
+        let mut written = vec![];
+        for table in &ordered{
+            let mut index = 0;
+            for e in &self.entries{
+                if &&e.table == table && !e.removed{
+                    if e.snapshot.is_none(){
+                        written.push((index, try!(self.insert(e))));
+                    }else{
+                        match try!(self.update(e)){
+                            Some(dao) => written.push((index, dao)),
+                            None => (),
+                        }
+                    }
+                }
+                index += 1;
+            }
+        }
+        for table in ordered.iter().rev(){
+            for e in &self.entries{
+                if &&e.table == table && e.removed && e.snapshot.is_some(){
+                    try!(self.delete(e));
+                }
+            }
+        }
+        Ok(written)
+    }
+
+    /// insert the record, columns which are not set are left to the database defaults
+    fn insert(&self, entry:&Entry)->Result<Dao, FlushError>{
+        let table = &entry.table;
+        let mut q = Query::insert();
+        q.into_table(table);
+        let mut included = vec![];
+        for c in &table.columns{
+            if included.contains(&c.name){
+                continue;
+            }
+            match entry.dao.values.get(&c.name){
+                Some(&Type::Null) | None => (),
+                Some(value) => {
+                    q.enumerate_column(&c.name);
+                    q.add_value(Operand::Value(value.clone()));
+                    included.push(c.name.to_string());
+                }
+            }
+        }
+        q.enumerate_all_table_column_as_return(table);
+        let sql_frag = self.db.build_query(&q);
+        match self.db.try_execute_sql_with_return(&sql_frag.sql, &sql_frag.params){
+            Ok(mut inserted) => {
+                if inserted.len() == 1{
+                    Ok(inserted.remove(0))
+                }else{
+                    Err(FlushError::Database(format!("Expecting 1 inserted record of {} but got {}", table.complete_name(), inserted.len())))
+                }
+            },
+            Err(e) => Err(FlushError::Database(e)),
+        }
+    }
+
+    /// update only the changed columns of the record,
+    /// the version column is also set when the table has one
+    /// returns the new values of the record, None when nothing is changed
+    fn update(&self, entry:&Entry)->Result<Option<Dao>, FlushError>{
+        let changed = Self::changed_columns(entry);
+        if changed.is_empty(){
+            return Ok(None);
+        }
+        let table = &entry.table;
+        let mut dao = entry.dao.clone();
+        let mut q = Query::update();
+        q.from(table);
+        for c in &changed{
+            q.enumerate_column(c);
+            q.add_value(Operand::Value(entry.dao.get_value(c)));
+        }
+        match table.version{
+            Some(ref version) => {
+                let snapshot = entry.snapshot.as_ref().unwrap();
+                let next = try!(Self::next_version(version, snapshot.get_ref(version.column())));
+                q.enumerate_column(version.column());
+                q.add_value(Operand::Value(next.clone()));
+                dao.set_value(version.column(), next);
+            },
+            None => (),
+        }
+        Self::filter_primary(&mut q, entry);
+        let updated = match q.execute(self.db){
+            Ok(updated) => updated,
+            Err(e) => return Err(FlushError::Database(e)),
+        };
+        try!(Self::check_conflict(entry, updated));
+        Ok(Some(dao))
+    }
+
+    fn delete(&self, entry:&Entry)->Result<usize, FlushError>{
+        let mut q = Query::delete();
+        q.from(&entry.table);
+        Self::filter_primary(&mut q, entry);
+        let deleted = match q.execute(self.db){
+            Ok(deleted) => deleted,
+            Err(e) => return Err(FlushError::Database(e)),
+        };
+        try!(Self::check_conflict(entry, deleted));
+        Ok(deleted)
+    }
+
+    /// filter the query using the primary key values of the snapshot,
+    /// and the version value of the snapshot when the table has a version column,
+    /// a version which is not yet set is matched with `IS NULL`
+    fn filter_primary(q:&mut Query, entry:&Entry){
+        let snapshot = entry.snapshot.as_ref().unwrap();
+        for p in entry.table.primary_columns(){
+            q.filter(&p.name, Equality::EQ, snapshot.get_ref(&p.name));
+        }
+        match entry.table.version{
+            Some(ref version) => {
+                match snapshot.values.get(version.column()){
+                    Some(&Type::Null) | None => {
+                        q.add_filter(Filter::is_null(version.column()));
+                    },
+                    Some(value) => {
+                        q.filter(version.column(), Equality::EQ, value);
+                    },
+                }
+            },
+            None => (),
+        }
+    }
+
+    /// no affected record on a versioned table means the record has been changed
+    /// or deleted by some other transaction
+    fn check_conflict(entry:&Entry, affected:usize)->Result<(), FlushError>{
+        if entry.table.version.is_some() && affected == 0{
+            return Err(FlushError::Conflict{
+                        table: entry.table.complete_name(),
+                        key: entry.key().unwrap_or(String::new()),
+                    });
+        }
+        Ok(())
+    }
+
+    /// the incremented version number, or the current time for timestamp versions
+    fn next_version(version:&Version, current:&Type)->Result<Type, FlushError>{
+        match (version, current){
+            (&Version::Number(_), &Type::I16(x)) => Ok(Type::I16(x + 1)),
+            (&Version::Number(_), &Type::I32(x)) => Ok(Type::I32(x + 1)),
+            (&Version::Number(_), &Type::I64(x)) => Ok(Type::I64(x + 1)),
+            (&Version::Number(_), &Type::Null) => Ok(Type::I32(1)),
+            (&Version::Timestamp(_), &Type::NaiveDateTime(_)) => Ok(Type::NaiveDateTime(Self::now().naive_utc())),
+            (&Version::Timestamp(_), _) => Ok(Type::DateTime(Self::now())),
+            (_, _) => Err(FlushError::Database(format!("Unsupported version column type {:?}", current))),
+        }
+    }
+
+    /// the current time truncated to microseconds, the precision of timestamps in the database,
+    /// so the version kept in the snapshot is the same as the stored value
+    fn now()->DateTime<UTC>{
+        let now = UTC::now();
+        now.with_nanosecond(now.nanosecond() / 1000 * 1000).unwrap()
+    }
+
+    /// order the tables such that the referred tables come first before the tables referring to them
+    /// tables with circular references are kept in their original order
+    fn dependency_order(tables:&Vec<Table>)->Vec<&Table>{
+        let mut ordered:Vec<&Table> = vec![];
+        while ordered.len() < tables.len(){
+            let mut added = false;
+            for t in tables{
+                if ordered.contains(&t){
+                    continue;
+                }
+                let mut ready = true;
+                for c in t.foreign_columns(){
+                    let foreign = c.foreign.as_ref().unwrap();
+                    for other in tables{
+                        if other != t && other.name == foreign.table
+                            && other.schema == foreign.schema
+                            && !ordered.contains(&other){
+                            ready = false;
+                        }
+                    }
+                }
+                if ready{
+                    ordered.push(t);
+                    added = true;
+                }
+            }
+            if !added{
+                for t in tables{
+                    if !ordered.contains(&t){
+                        ordered.push(t);
+                    }
+                }
+            }
+        }
+        ordered
+    }
+}
+
+#[test]
+fn test_dependency_order(){
+    use table::fixture::{table, column};
+    let product = table("product", vec![column("product_id", true, None)]);
+    let category = table("category", vec![column("category_id", true, None)]);
+    let product_category = table("product_category", vec![
+        column("product_id", true, Some(("product", "product_id"))),
+        column("category_id", true, Some(("category", "category_id"))),
+        ]);
+    let tables = vec![product_category.clone(), product.clone(), category.clone()];
+    let ordered = Session::dependency_order(&tables);
+    assert_eq!(ordered, vec![&product, &category, &product_category]);
+}
+
+#[test]
+fn test_next_version(){
+    let version = Version::Number("version".to_string());
+    assert_eq!(Session::next_version(&version, &Type::I32(1)).unwrap(), Type::I32(2));
+    assert_eq!(Session::next_version(&version, &Type::I64(41)).unwrap(), Type::I64(42));
+    assert!(Session::next_version(&version, &Type::String("1".to_string())).is_err());
+    let updated = Version::Timestamp("updated".to_string());
+    match Session::next_version(&updated, &Type::Null).unwrap(){
+        Type::DateTime(now) => assert_eq!(now.nanosecond() % 1000, 0),
+        other => panic!("expecting a timestamp but got {:?}", other),
+    }
+}
+
+#[test]
+fn test_filter_null_version(){
+    use platform::postgres::Postgres;
+    use table::fixture::{table, column};
+    let mut table = table("product", vec![column("product_id", true, None)]);
+    table.with_version(Version::Number("version".to_string()));
+    let mut snapshot = Dao::new();
+    snapshot.set("product_id", &1);
+    snapshot.set_value("version", Type::Null);
+    let entry = Entry{table: table, snapshot: Some(snapshot.clone()), dao: snapshot, removed: false};
+    let mut q = Query::delete();
+    q.from(&entry.table);
+    Session::filter_primary(&mut q, &entry);
+    let sql = Postgres::new().build_query(&q).sql;
+    assert!(sql.contains("version IS NULL"));
+    assert!(!sql.contains("version ="));
+}
+
+#[test]
+fn test_updated_timestamp_version(){
+    use platform::postgres::Postgres;
+    use table::fixture::{table, column};
+    let mut updated = column("updated", false, None);
+    updated.data_type = "DateTime<UTC>".to_string();
+    updated.db_data_type = "timestamp with time zone".to_string();
+    let mut table = table("product", vec![column("product_id", true, None), updated]);
+    // only the column named version is detected, the updated column is set explicitly
+    assert_eq!(Version::detect(&table.columns), None);
+    table.with_version(Version::Timestamp("updated".to_string()));
+    let mut snapshot = Dao::new();
+    snapshot.set("product_id", &1);
+    snapshot.set_value("updated", Type::DateTime(UTC::now()));
+    let entry = Entry{table: table, snapshot: Some(snapshot.clone()), dao: snapshot, removed: false};
+    let mut q = Query::delete();
+    q.from(&entry.table);
+    Session::filter_primary(&mut q, &entry);
+    let sql_frag = Postgres::new().build_query(&q);
+    assert!(sql_frag.sql.contains("updated = $2"));
+    assert_eq!(sql_frag.params.len(), 2);
+}
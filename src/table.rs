@@ -22,6 +22,10 @@ pub struct Column{
     pub foreign:Option<Foreign>,
     ///determines if the column is inherited from the parent table
     pub is_inherited:bool,
+    /// the total number of digits of numeric columns, ie: 10 in numeric(10,2)
+    pub precision:Option<u32>,
+    /// the number of digits after the decimal point of numeric columns, ie: 2 in numeric(10,2)
+    pub scale:Option<u32>,
 }
 
 impl Column{
@@ -99,8 +103,9 @@ pub struct RefTable<'a>{
     /// the table being referred
     pub table: &'a Table,
     /// the referring column, applicable to direct has_one
-    column: Option<&'a Column>,
-    linker_table: Option<&'a Table>,
+    pub column: Option<&'a Column>,
+    /// the table linking the 2 tables, applicable to indirect has_many
+    pub linker_table: Option<&'a Table>,
     pub is_ext: bool,
     pub is_has_one: bool,
     pub is_has_many: bool,
@@ -160,6 +165,44 @@ impl <'a>RefTable<'a>{
 }
 
 
+/// the column used for optimistic locking,
+/// updates are only applied when this column is unchanged since the record was retrieved
+#[derive(Debug, Clone, PartialEq)]
+pub enum Version{
+    /// an integer column, incremented on every update
+    Number(String),
+    /// a timestamp column, set to the current time on every update, ie: updated
+    Timestamp(String),
+}
+
+impl Version{
+    
+    /// the name of the version column
+    pub fn column(&self)->&str{
+        match *self{
+            Version::Number(ref column) => column,
+            Version::Timestamp(ref column) => column,
+        }
+    }
+
+    /// the version column of the table by convention, which is the column named `version`,
+    /// a number when it is an integer column or a timestamp when it is a timestamp column.
+    /// Other columns ie: an `updated` timestamp are not detected,
+    /// set them with `Table::with_version` or the `version` attribute of the derive
+    pub fn detect(columns:&Vec<Column>)->Option<Version>{
+        for c in columns{
+            if c.name == "version"{
+                match &c.data_type[..]{
+                    "i16" | "i32" | "i64" => return Some(Version::Number(c.name.to_string())),
+                    "DateTime<UTC>" | "NaiveDateTime" => return Some(Version::Timestamp(c.name.to_string())),
+                    _ => (),
+                }
+            }
+        }
+        None
+    }
+}
+
 #[derive(Debug)]
 #[derive(Clone)]
 pub struct Table{
@@ -185,6 +228,9 @@ pub struct Table{
 
     ///columns of this table
     pub columns:Vec<Column>,
+    
+    ///the column used for optimistic locking, None when not used
+    pub version:Option<Version>,
 
 }
 impl fmt::Display for Table {
@@ -206,6 +252,13 @@ impl PartialEq for Table{
 
 impl Table{
     
+    /// use optimistic locking when updating records of this table,
+    /// needed when the version column is not named `version` ie: `Version::Timestamp("updated")`
+    pub fn with_version(&mut self, version:Version)->&mut Self{
+        self.version = Some(version);
+        self
+    }
+    
     /// return the long name of the table using schema.table_name
     pub fn complete_name(&self)->String{
         format!("{}.{}", self.schema, self.name)
@@ -620,7 +673,47 @@ impl Table{
 }
 
 
-fn capitalize(str:&str)->String{
+/// tables of the bazaar schema for the tests of the modules using tables
+#[cfg(test)]
+pub mod fixture{
+    use super::{Table, Column, Foreign};
+
+    /// an integer column, referring to the (table, column) of the bazaar schema when foreign
+    pub fn column(name:&str, is_primary:bool, foreign:Option<(&str, &str)>)->Column{
+        Column{
+            name: name.to_string(),
+            data_type: "i32".to_string(),
+            db_data_type: "integer".to_string(),
+            is_primary: is_primary,
+            is_unique: false,
+            default: None,
+            comment: None,
+            not_null: true,
+            foreign: foreign.map(|(table, column)| Foreign{
+                    schema: "bazaar".to_string(),
+                    table: table.to_string(),
+                    column: column.to_string(),
+                }),
+            is_inherited: false,
+            precision: None,
+            scale: None,
+        }
+    }
+
+    pub fn table(name:&str, columns:Vec<Column>)->Table{
+        Table{
+            schema: "bazaar".to_string(),
+            name: name.to_string(),
+            parent_table: None,
+            sub_table: vec![],
+            comment: None,
+            columns: columns,
+            version: None,
+        }
+    }
+}
+
+pub fn capitalize(str:&str)->String{
      str.chars().take(1)
          .flat_map(char::to_uppercase)
         .chain(str.chars().skip(1))
@@ -0,0 +1,232 @@
+//! Values of the postgresql types which have no equivalent in std or chrono:
+//! intervals, network addresses, money, points and ranges
+use rustc_serialize::{Encodable, Encoder, Decodable, Decoder};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// a time span, months and days are kept apart from the time
+/// since their length depends on the date they are added to, the same as in postgresql
+#[derive(RustcDecodable, RustcEncodable)]
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub struct Interval{
+    pub months:i32,
+    pub days:i32,
+    pub microseconds:i64,
+}
+
+impl Interval{
+
+    pub fn new(months:i32, days:i32, microseconds:i64)->Self{
+        Interval{months: months, days: days, microseconds: microseconds}
+    }
+
+    pub fn from_seconds(seconds:i64)->Self{
+        Interval::new(0, 0, seconds * 1_000_000)
+    }
+}
+
+/// an ip address of an `inet` or a `cidr` column, with the number of bits in the netmask,
+/// ie: 192.168.1.0/24
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub struct Inet{
+    pub addr:IpAddr,
+    pub netmask:u8,
+}
+
+impl Inet{
+
+    /// a single host, the netmask covers the whole address
+    pub fn host(addr:IpAddr)->Self{
+        let netmask = match addr{
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        Inet{addr: addr, netmask: netmask}
+    }
+}
+
+impl fmt::Display for Inet{
+    fn fmt(&self, f: &mut fmt::Formatter)->fmt::Result{
+        write!(f, "{}/{}", self.addr, self.netmask)
+    }
+}
+
+impl FromStr for Inet{
+    type Err = String;
+
+    /// parse `address[/netmask]`
+    fn from_str(s: &str)->Result<Self, String>{
+        let mut split = s.trim().splitn(2, '/');
+        let addr = match split.next().unwrap().parse(){
+            Ok(addr) => addr,
+            Err(_) => return Err(format!("Invalid ip address {}", s)),
+        };
+        let inet = Inet::host(addr);
+        match split.next(){
+            Some(netmask) => match netmask.parse(){
+                Ok(netmask) if netmask <= inet.netmask => Ok(Inet{addr: inet.addr, netmask: netmask}),
+                _ => Err(format!("Invalid netmask {}", s)),
+            },
+            None => Ok(inet),
+        }
+    }
+}
+
+/// encoded as the text, ie: "192.168.1.0/24"
+impl Encodable for Inet{
+    fn encode<S: Encoder>(&self, s: &mut S)->Result<(), S::Error>{
+        s.emit_str(&self.to_string())
+    }
+}
+
+impl Decodable for Inet{
+    fn decode<D: Decoder>(d: &mut D)->Result<Self, D::Error>{
+        let text = try!(d.read_str());
+        Inet::from_str(&text).map_err(|e| d.error(&e))
+    }
+}
+
+/// a 6 byte mac address of a `macaddr` column
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub struct MacAddr(pub [u8; 6]);
+
+impl fmt::Display for MacAddr{
+    fn fmt(&self, f: &mut fmt::Formatter)->fmt::Result{
+        let b = &self.0;
+        write!(f, "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", b[0], b[1], b[2], b[3], b[4], b[5])
+    }
+}
+
+impl FromStr for MacAddr{
+    type Err = String;
+
+    /// parse `08:00:2b:01:02:03` or `08-00-2b-01-02-03`
+    fn from_str(s: &str)->Result<Self, String>{
+        let parts:Vec<&str> = s.trim().split(|c| c == ':' || c == '-').collect();
+        if parts.len() != 6{
+            return Err(format!("Invalid mac address {}", s));
+        }
+        let mut bytes = [0u8; 6];
+        for (i, part) in parts.iter().enumerate(){
+            match u8::from_str_radix(part, 16){
+                Ok(b) => bytes[i] = b,
+                Err(_) => return Err(format!("Invalid mac address {}", s)),
+            }
+        }
+        Ok(MacAddr(bytes))
+    }
+}
+
+/// encoded as the text, ie: "08:00:2b:01:02:03"
+impl Encodable for MacAddr{
+    fn encode<S: Encoder>(&self, s: &mut S)->Result<(), S::Error>{
+        s.emit_str(&self.to_string())
+    }
+}
+
+impl Decodable for MacAddr{
+    fn decode<D: Decoder>(d: &mut D)->Result<Self, D::Error>{
+        let text = try!(d.read_str());
+        MacAddr::from_str(&text).map_err(|e| d.error(&e))
+    }
+}
+
+/// an amount of a `money` column in the smallest unit of the currency, ie: cents,
+/// the number of fractional digits is set by lc_monetary in the database, 2 for most locales
+#[derive(RustcDecodable, RustcEncodable)]
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub struct Money(pub i64);
+
+impl fmt::Display for Money{
+    /// formatted with 2 fractional digits
+    fn fmt(&self, f: &mut fmt::Formatter)->fmt::Result{
+        let sign = if self.0 < 0{ "-" }else{ "" };
+        write!(f, "{}{}.{:02}", sign, (self.0 / 100).abs(), (self.0 % 100).abs())
+    }
+}
+
+/// a point in the plane of a `point` column
+#[derive(RustcDecodable, RustcEncodable)]
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub struct Point{
+    pub x:f64,
+    pub y:f64,
+}
+
+/// a bound of a range
+#[derive(RustcDecodable, RustcEncodable)]
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum RangeBound<T>{
+    /// `[` or `]`, the value is in the range
+    Inclusive(T),
+    /// `(` or `)`, the value is not in the range
+    Exclusive(T),
+    /// no limit, ie: the lower bound of `(,2015-12-31]`
+    Unbounded,
+}
+
+impl <T>RangeBound<T>{
+
+    pub fn map<U, F: Fn(&T)->U>(&self, f: F)->RangeBound<U>{
+        match *self{
+            RangeBound::Inclusive(ref x) => RangeBound::Inclusive(f(x)),
+            RangeBound::Exclusive(ref x) => RangeBound::Exclusive(f(x)),
+            RangeBound::Unbounded => RangeBound::Unbounded,
+        }
+    }
+}
+
+/// the value of a range column ie: `tsrange`, `tstzrange`, `daterange`, `int4range`, `int8range`, `numrange`
+#[derive(RustcDecodable, RustcEncodable)]
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum Range<T>{
+    /// the range with no values, ie: `'empty'::daterange`
+    Empty,
+    /// the values from the lower bound up to the upper bound
+    Between(RangeBound<T>, RangeBound<T>),
+}
+
+impl <T>Range<T>{
+
+    /// `[lower, upper)`, the canonical form used by postgresql
+    pub fn new(lower:T, upper:T)->Self{
+        Range::Between(RangeBound::Inclusive(lower), RangeBound::Exclusive(upper))
+    }
+
+    pub fn map<U, F: Fn(&T)->U>(&self, f: F)->Range<U>{
+        match *self{
+            Range::Empty => Range::Empty,
+            Range::Between(ref lower, ref upper) => Range::Between(lower.map(&f), upper.map(&f)),
+        }
+    }
+}
+
+#[test]
+fn test_types(){
+    let inet = Inet::from_str("192.168.1.0/24").unwrap();
+    assert_eq!(inet.netmask, 24);
+    assert_eq!(inet.to_string(), "192.168.1.0/24");
+    assert_eq!(Inet::from_str("::1").unwrap().netmask, 128);
+    assert!(Inet::from_str("192.168.1.0/33").is_err());
+    let mac = MacAddr::from_str("08-00-2B-01-02-03").unwrap();
+    assert_eq!(mac.to_string(), "08:00:2b:01:02:03");
+    assert!(MacAddr::from_str("08:00:2b").is_err());
+    assert_eq!(Money(-1205).to_string(), "-12.05");
+    let range = Range::new(1, 10).map(|x| *x as i64 * 2);
+    assert_eq!(range, Range::Between(RangeBound::Inclusive(2i64), RangeBound::Exclusive(20)));
+}
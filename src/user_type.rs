@@ -0,0 +1,89 @@
+use table::{Column, capitalize};
+
+/// the module of the generated code in the crate using it, ie: `mod gen;`
+pub const GENERATED_MODULE: &'static str = "gen";
+
+/// the kind of user defined type and its definition
+#[derive(Debug, Clone, PartialEq)]
+pub enum UserTypeKind{
+    /// `CREATE TYPE .. AS ENUM`, the labels in the order they are defined
+    Enum(Vec<String>),
+    /// `CREATE DOMAIN .. AS`, the database data type it is based on
+    Domain(String),
+    /// `CREATE TYPE .. AS (..)`, the attributes in the order they are defined
+    Composite(Vec<Column>),
+}
+
+/// a user defined type in the database, ie: enums, domains and composite types
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserType{
+    pub schema:String,
+    pub name:String,
+    pub kind:UserTypeKind,
+    pub comment:Option<String>,
+}
+
+impl UserType{
+
+    /// schema.name
+    pub fn complete_name(&self)->String{
+        format!("{}.{}", self.schema, self.name)
+    }
+
+    /// whether the database data type of a column refers to this type,
+    /// the type is schema qualified when it is not in the search path
+    pub fn is_named(&self, db_type:&str)->bool{
+        db_type == self.name || db_type == self.complete_name()
+    }
+
+    /// the name of the generated rust type
+    /// mood_type -> MoodType
+    pub fn type_name(&self)->String{
+        to_camel_case(&self.name)
+    }
+
+    /// the path the generated structs import the type from,
+    /// the type is generated in the module of its schema, the same as the tables
+    /// ie: gen::bazaar::ProductStatus
+    pub fn import_path(&self)->String{
+        format!("{}::{}::{}", GENERATED_MODULE, self.schema, self.type_name())
+    }
+
+    /// the name of the rust enum variant of the enum label
+    /// "very happy" -> VeryHappy, "1st" -> V1st
+    pub fn variant_name(label:&str)->String{
+        let variant = to_camel_case(label);
+        if variant.is_empty(){
+            "Empty".to_string()
+        }else if variant.chars().next().unwrap().is_numeric(){
+            format!("V{}", variant)
+        }else{
+            variant
+        }
+    }
+}
+
+fn to_camel_case(name:&str)->String{
+    let mut camel = String::new();
+    for i in name.split(|c:char| !c.is_alphanumeric()){
+        camel.push_str(&capitalize(i));
+    }
+    camel
+}
+
+#[test]
+fn test_names(){
+    let user_type = UserType{
+        schema: "bazaar".to_string(),
+        name: "product_status".to_string(),
+        kind: UserTypeKind::Enum(vec!["on sale".to_string()]),
+        comment: None,
+    };
+    assert_eq!(user_type.type_name(), "ProductStatus");
+    assert_eq!(user_type.import_path(), "gen::bazaar::ProductStatus");
+    assert!(user_type.is_named("bazaar.product_status"));
+    assert!(user_type.is_named("product_status"));
+    assert_eq!(UserType::variant_name("on sale"), "OnSale");
+    assert_eq!(UserType::variant_name("1st-class"), "V1stClass");
+    assert_eq!(UserType::variant_name(""), "Empty");
+}
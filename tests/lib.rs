@@ -1 +1,5 @@
-mod test_pool;
\ No newline at end of file
+mod test_pool;
+mod test_ssl;
+mod test_cursor;
+mod test_statement_cache;
+mod test_range;
@@ -0,0 +1,85 @@
+extern crate rustorm;
+#[macro_use]
+extern crate rustorm_derive;
+extern crate uuid;
+
+use uuid::Uuid;
+use rustorm::dao::{Dao, IsDao, ToDao};
+use rustorm::table::{IsTable, Version};
+
+#[derive(Debug, Clone, PartialEq, IsTable, IsDao)]
+#[rustorm(table = "product", schema = "bazaar")]
+pub struct ProductSummary{
+    #[rustorm(primary)]
+    pub product_id: Uuid,
+    #[rustorm(column = "name")]
+    pub product_name: String,
+    pub description: Option<String>,
+    #[rustorm(db_type = "numeric")]
+    pub price: Option<f64>,
+    #[rustorm(skip)]
+    pub selected: bool,
+}
+
+#[derive(Debug, Clone, IsTable, IsDao)]
+pub struct ProductAvailability{
+    pub product_id: Uuid,
+    pub available: Option<bool>,
+    #[rustorm(version)]
+    pub revision: i32,
+}
+
+#[derive(Debug, Clone, IsTable, IsDao)]
+pub struct Category{
+    pub category_id: ::uuid::Uuid,
+    pub version: i64,
+}
+
+#[test]
+fn test_derive_table(){
+    let table = ProductSummary::table();
+    assert_eq!(table.schema, "bazaar");
+    assert_eq!(table.name, "product");
+    let names:Vec<&str> = table.columns.iter().map(|c| &c.name[..]).collect();
+    assert_eq!(names, vec!["product_id", "name", "description", "price"]);
+    assert!(table.columns[0].is_primary);
+    assert!(table.columns[0].not_null);
+    assert_eq!(table.columns[0].data_type, "Uuid");
+    assert_eq!(table.columns[0].db_data_type, "uuid");
+    assert!(!table.columns[2].not_null);
+    assert_eq!(table.columns[2].data_type, "String");
+    assert_eq!(table.columns[3].db_data_type, "numeric");
+}
+
+#[test]
+fn test_derive_default_table_name(){
+    let table = ProductAvailability::table();
+    assert_eq!(table.schema, "public");
+    assert_eq!(table.name, "product_availability");
+}
+
+#[test]
+fn test_derive_version(){
+    assert_eq!(ProductSummary::table().version, None);
+    assert_eq!(ProductAvailability::table().version, Some(Version::Number("revision".to_string())));
+    // the column named version is used when there is no version attribute
+    let table = Category::table();
+    assert_eq!(table.version, Some(Version::Number("version".to_string())));
+    assert_eq!(table.columns[0].db_data_type, "uuid");
+}
+
+#[test]
+fn test_derive_dao(){
+    let product = ProductSummary{
+        product_id: Uuid::new_v4(),
+        product_name: "iphone".to_string(),
+        description: None,
+        price: Some(699.0),
+        selected: true,
+    };
+    let dao = product.to_dao();
+    assert_eq!(dao.get::<String>("name"), "iphone");
+    assert!(!dao.values.contains_key("selected"));
+    let back = ProductSummary::from_dao(&dao);
+    assert_eq!(back, ProductSummary{selected: false, ..product});
+}